@@ -0,0 +1,826 @@
+use crate::route_selector::{parse_route_type, RouteSelector};
+use crate::timetable::name_rules::NamePolicy;
+use crate::timetable::stop_sheet::SheetFormat;
+use crate::timetable::Direction;
+
+pub const USAGE: &str = "\
+usage: morningstar_parser <command> [options]
+
+commands:
+    extract <gtfs>        extract routes from a GTFS feed into a timetable file
+        -r, --route <id>      route id to extract (repeatable)
+        -l, --line <number>   route short name to extract (repeatable)
+        --name <text>         only routes whose long name contains <text>
+        --agency <agency>     only routes of this agency (id or name)
+        --mode <mode>         only routes of this mode (bus, tram, rail...)
+        --all                 extract every route matched by a line or filter
+        --from <stop>         origin stop id, station id or name (repeatable)
+        --to <stop>           destination stop id, station id or name (repeatable)
+                              extract the trips going from an origin to a
+                              destination instead of whole routes
+        -o, --output <file>   output file (default: timetable.ron)
+        -f, --format <fmt>    `timetable` (default) or `model`, the compact mode:
+                              stops known by name only, a single time per
+                              stop, no headsign or direction, and the stops
+                              after 24:00:00 left out
+        --names <policies>    how to pick the spelling of stops named alike, as
+                              a comma separated list of `diacritics`,
+                              `most-frequent`, `parent-station` and `longest`
+                              tried in order (default: longest)
+        --name-overrides <file>
+                              RON map of stop ids or names to the name to use
+        --rename-report <file>
+                              file to list every stop rename in
+        --display-rules <file>
+                              RON file of abbreviations and maximum length for
+                              the display names of stops
+        --max-name-length <count>
+                              most characters of a display name
+    inspect <gtfs>        list the agencies and routes of a GTFS feed
+        -l, --line, --name, --agency, --mode: only list the matching routes
+    departures <file>     list the journeys running on a given day
+        -d, --date <date>     day to look at, as YYYY-MM-DD (default: today)
+        -f, --format <fmt>    `timetable` (default) or `model`
+    next <file>           list the next departures from a stop of a timetable,
+                          not of a model
+        --from <stop>         stop id, station id or name to leave from
+        --to <stop>           stop id, station id or name to go to (optional)
+        --direction <dir>     only trips going `outbound` or `inbound`
+        -d, --date <date>     day to leave on, as YYYY-MM-DD (default: today)
+        -t, --time <time>     time to leave at, as HH:MM (default: now)
+        -n, --count <count>   number of departures to list (default: 5)
+    patterns <file>       list the route patterns of a timetable, not of a
+                          model, with their trips of the day
+        -d, --date <date>     day to look at, as YYYY-MM-DD (default: today)
+    plan <file>           plan journeys between two stops of a timetable, not
+                          of a model, changing trips on the way if needed
+        --from <stop>         stop id, station id or name to leave from
+        --to <stop>           stop id, station id or name to go to
+        -d, --date <date>     day to leave on, as YYYY-MM-DD (default: today)
+        -t, --time <time>     leave at or after, as HH:MM (default: now)
+        --transfers <count>   most changes between trips (default: 1)
+    round-trip <file>     list round trips between two stops of a timetable, not
+                          of a model, with the time spent at the destination
+                          and the last way back
+        --from <stop>         stop id, station id or name to leave from
+        --to <stop>           stop id, station id or name to go to
+        -d, --date <date>     day to leave on, as YYYY-MM-DD (default: today)
+        -t, --time <time>     leave at or after, as HH:MM (default: 00:00)
+        --until <time>        leave at or before, as HH:MM (default: 23:59)
+        --stay <minutes>      shortest stay at the destination (default: 0)
+    search <file> <name>  search the stops of a timetable, not of a model, by
+                          name, ignoring case and accents and tolerating typos
+    sheet <file>          print the timetable of a stop of a timetable, not of a
+                          model, by day type
+        --from <stop>         stop id, station id or name
+        -d, --date <date>     first day the sheet is valid on, as YYYY-MM-DD
+                              (default: today)
+        --direction <dir>     only trips going `outbound` or `inbound`
+        --pattern <id>        only trips of a route pattern, see `patterns`
+        --as <fmt>            `text` (default), `html` or `markdown`
+        -o, --output <file>   file to write to (default: standard output)
+    stations <file>       list the stations of a timetable, not of a model, with
+                          the stops they group
+    stops <file>          list the stops served on a given day, with their ids
+                          and platforms for the timetable format
+        -d, --date <date>     day to look at, as YYYY-MM-DD (default: today)
+        -f, --format <fmt>    `timetable` (default) or `model`
+    validate <file>       check a timetable file for inconsistencies
+        -f, --format <fmt>    `timetable` (default) or `model`
+        --feed <gtfs>         also check that every calendar date of the
+                              extracted services made it into the file
+    help                  print this message";
+
+/// Which of the two timetable representations a file holds, `Timetable`
+/// unless told otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `morningstar_model::TimeTable`, the compact representation, which only
+    /// knows stops by their name and keeps a single time per stop, without
+    /// headsigns or directions. Its times can't go past 24:00:00, stops
+    /// served after that are left out.
+    Model,
+    /// `timetable::Timetable`, which keeps the GTFS identifiers around, stops
+    /// included along with their parent station and platform, as well as both
+    /// the arrival and departure times at each stop.
+    Timetable,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "model" => Ok(Self::Model),
+            "timetable" => Ok(Self::Timetable),
+            _ => Err(format!(
+                "unknown format `{s}`, expected `model` or `timetable`"
+            )),
+        }
+    }
+}
+
+/// How `extract` uniformises and shortens stop names, timetable format
+/// only.
+#[derive(Debug, Default)]
+pub struct Naming {
+    pub policies: Option<Vec<NamePolicy>>,
+    pub overrides: Option<String>,
+    pub report: Option<String>,
+    pub display_rules: Option<String>,
+    pub max_length: Option<usize>,
+}
+
+/// What part of the feed `extract` keeps.
+#[derive(Debug)]
+pub enum Selection {
+    Routes {
+        selectors: Vec<RouteSelector>,
+        all: bool,
+    },
+    StopPairs {
+        origins: Vec<String>,
+        destinations: Vec<String>,
+    },
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Extract {
+        feed: String,
+        selection: Selection,
+        output: String,
+        format: Format,
+        naming: Naming,
+    },
+    Inspect {
+        feed: String,
+        routes: Vec<RouteSelector>,
+    },
+    Departures {
+        timetable: String,
+        format: Format,
+        date: chrono::NaiveDate,
+    },
+    Next {
+        timetable: String,
+        from: Vec<String>,
+        to: Vec<String>,
+        direction: Option<Direction>,
+        at: chrono::NaiveDateTime,
+        count: usize,
+    },
+    Patterns {
+        timetable: String,
+        date: chrono::NaiveDate,
+    },
+    Plan {
+        timetable: String,
+        from: Vec<String>,
+        to: Vec<String>,
+        at: chrono::NaiveDateTime,
+        max_transfers: usize,
+    },
+    RoundTrip {
+        timetable: String,
+        from: Vec<String>,
+        to: Vec<String>,
+        leave_after: chrono::NaiveDateTime,
+        leave_before: chrono::NaiveDateTime,
+        min_stay: chrono::Duration,
+    },
+    Search {
+        timetable: String,
+        query: String,
+    },
+    Sheet {
+        timetable: String,
+        stops: Vec<String>,
+        from: chrono::NaiveDate,
+        direction: Option<Direction>,
+        pattern: Option<String>,
+        format: SheetFormat,
+        output: Option<String>,
+    },
+    Stations {
+        timetable: String,
+    },
+    Stops {
+        timetable: String,
+        format: Format,
+        date: chrono::NaiveDate,
+    },
+    Validate {
+        timetable: String,
+        format: Format,
+        feed: Option<String>,
+    },
+    Help,
+}
+
+/// Options shared by every subcommand; each subcommand only looks at the
+/// ones it documents and rejects the others.
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    routes: Vec<String>,
+    lines: Vec<String>,
+    long_name: Option<String>,
+    agency: Option<String>,
+    route_type: Option<gtfs_structures::RouteType>,
+    all_routes: bool,
+    origins: Vec<String>,
+    destinations: Vec<String>,
+    output: Option<String>,
+    feed: Option<String>,
+    format: Option<Format>,
+    date: Option<chrono::NaiveDate>,
+    time: Option<chrono::NaiveTime>,
+    count: Option<usize>,
+    until: Option<chrono::NaiveTime>,
+    stay: Option<i64>,
+    transfers: Option<usize>,
+    direction: Option<Direction>,
+    pattern: Option<String>,
+    sheet_format: Option<SheetFormat>,
+    naming: Naming,
+    help: bool,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("{name} expects a value"));
+            match arg.as_str() {
+                "-r" | "--route" => options.routes.push(value(&arg)?),
+                "-l" | "--line" => options.lines.push(value(&arg)?),
+                "--name" => options.long_name = Some(value(&arg)?),
+                "--agency" => options.agency = Some(value(&arg)?),
+                "--mode" => options.route_type = Some(parse_route_type(&value(&arg)?)?),
+                "--all" => options.all_routes = true,
+                "--from" => options.origins.push(value(&arg)?),
+                "--to" => options.destinations.push(value(&arg)?),
+                "-o" | "--output" => options.output = Some(value(&arg)?),
+                "--feed" => options.feed = Some(value(&arg)?),
+                "-f" | "--format" => options.format = Some(value(&arg)?.parse()?),
+                "-d" | "--date" => {
+                    let date = value(&arg)?;
+                    let date = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                        .map_err(|error| format!("invalid date `{date}`: {error}"))?;
+                    options.date = Some(date);
+                }
+                "-t" | "--time" => {
+                    let time = value(&arg)?;
+                    let time = chrono::NaiveTime::parse_from_str(&time, "%H:%M")
+                        .map_err(|error| format!("invalid time `{time}`: {error}"))?;
+                    options.time = Some(time);
+                }
+                "--until" => {
+                    let time = value(&arg)?;
+                    let time = chrono::NaiveTime::parse_from_str(&time, "%H:%M")
+                        .map_err(|error| format!("invalid time `{time}`: {error}"))?;
+                    options.until = Some(time);
+                }
+                "--stay" => {
+                    let stay = value(&arg)?;
+                    let stay = stay
+                        .parse()
+                        .map_err(|error| format!("invalid stay `{stay}`: {error}"))?;
+                    options.stay = Some(stay);
+                }
+                "--transfers" => {
+                    let transfers = value(&arg)?;
+                    let transfers = transfers.parse().map_err(|error| {
+                        format!("invalid transfer count `{transfers}`: {error}")
+                    })?;
+                    options.transfers = Some(transfers);
+                }
+                "--direction" => options.direction = Some(value(&arg)?.parse()?),
+                "--pattern" => options.pattern = Some(value(&arg)?),
+                "--as" => options.sheet_format = Some(value(&arg)?.parse()?),
+                "--names" => {
+                    let policies = value(&arg)?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?;
+                    options.naming.policies = Some(policies);
+                }
+                "--name-overrides" => options.naming.overrides = Some(value(&arg)?),
+                "--rename-report" => options.naming.report = Some(value(&arg)?),
+                "--display-rules" => options.naming.display_rules = Some(value(&arg)?),
+                "--max-name-length" => {
+                    let length = value(&arg)?;
+                    let length = length
+                        .parse()
+                        .map_err(|error| format!("invalid length `{length}`: {error}"))?;
+                    options.naming.max_length = Some(length);
+                }
+                "-n" | "--count" => {
+                    let count = value(&arg)?;
+                    let count = count
+                        .parse()
+                        .map_err(|error| format!("invalid count `{count}`: {error}"))?;
+                    options.count = Some(count);
+                }
+                "-h" | "--help" => options.help = true,
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => options.positional.push(arg),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Takes the single positional argument of a subcommand.
+    fn file(&mut self, what: &str) -> Result<String, String> {
+        match self.positional.len() {
+            0 => Err(format!("missing {what}")),
+            1 => Ok(self.positional.remove(0)),
+            _ => Err(format!("unexpected argument `{}`", self.positional[1])),
+        }
+    }
+
+    fn has_route_filters(&self) -> bool {
+        self.long_name.is_some() || self.agency.is_some() || self.route_type.is_some()
+    }
+
+    /// Turns route ids, lines and filters into selectors: one per id and one
+    /// per line, each narrowed by the filters, or a single one made of the
+    /// filters when there are neither.
+    fn route_selectors(&self) -> Vec<RouteSelector> {
+        let filter = RouteSelector {
+            long_name: self.long_name.clone(),
+            agency: self.agency.clone(),
+            route_type: self.route_type,
+            ..Default::default()
+        };
+        let mut selectors: Vec<_> = self
+            .routes
+            .iter()
+            .map(|id| RouteSelector {
+                id: Some(id.clone()),
+                ..filter.clone()
+            })
+            .collect();
+        selectors.extend(self.lines.iter().map(|line| RouteSelector {
+            short_name: Some(line.clone()),
+            ..filter.clone()
+        }));
+        if selectors.is_empty() && self.has_route_filters() {
+            selectors.push(filter);
+        }
+        selectors
+    }
+
+    fn date_or_today(&self) -> chrono::NaiveDate {
+        self.date
+            .unwrap_or_else(|| chrono::Local::now().date_naive())
+    }
+
+    fn reject(&self, command: &str, unused: &[(&str, bool)]) -> Result<(), String> {
+        match unused.iter().find(|(_, is_set)| *is_set) {
+            Some((option, _)) => Err(format!("`{command}` does not take {option}")),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let Some(command) = args.next() else {
+        return Ok(Command::Help);
+    };
+    let mut options = Options::parse(args)?;
+    if options.help {
+        return Ok(Command::Help);
+    }
+    let has_routes = !options.routes.is_empty()
+        || !options.lines.is_empty()
+        || options.has_route_filters()
+        || options.all_routes;
+    let has_stops = !options.origins.is_empty() || !options.destinations.is_empty();
+    let has_output = options.output.is_some();
+    let has_format = options.format.is_some();
+    let has_date = options.date.is_some();
+    let has_feed = options.feed.is_some();
+    let has_names = options.naming.policies.is_some()
+        || options.naming.overrides.is_some()
+        || options.naming.report.is_some()
+        || options.naming.display_rules.is_some()
+        || options.naming.max_length.is_some();
+    let has_sheet = options.pattern.is_some() || options.sheet_format.is_some();
+    let has_query = options.time.is_some()
+        || options.count.is_some()
+        || options.until.is_some()
+        || options.stay.is_some()
+        || options.transfers.is_some()
+        || options.direction.is_some()
+        || has_sheet;
+    let has_window = options.until.is_some() || options.stay.is_some();
+    match command.as_str() {
+        "extract" => {
+            options.reject(
+                &command,
+                &[
+                    ("--date", has_date),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                ],
+            )?;
+            let format = options.format.unwrap_or(Format::Timetable);
+            if has_names && format == Format::Model {
+                return Err("name options do not apply to `--format model`".into());
+            }
+            let selection = if has_stops {
+                if has_routes {
+                    return Err("--from and --to cannot be combined with route options".into());
+                }
+                if options.origins.is_empty() || options.destinations.is_empty() {
+                    return Err("`extract` needs both --from and --to".into());
+                }
+                Selection::StopPairs {
+                    origins: std::mem::take(&mut options.origins),
+                    destinations: std::mem::take(&mut options.destinations),
+                }
+            } else {
+                let selectors = options.route_selectors();
+                if selectors.is_empty() {
+                    return Err("`extract` needs at least one --route, --line or filter".into());
+                }
+                Selection::Routes {
+                    selectors,
+                    all: options.all_routes,
+                }
+            };
+            Ok(Command::Extract {
+                feed: options.file("GTFS feed")?,
+                selection,
+                output: options.output.take().unwrap_or("timetable.ron".into()),
+                format,
+                naming: std::mem::take(&mut options.naming),
+            })
+        }
+        "inspect" => {
+            options.reject(
+                &command,
+                &[
+                    ("--all", options.all_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--date", has_date),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            Ok(Command::Inspect {
+                feed: options.file("GTFS feed")?,
+                routes: options.route_selectors(),
+            })
+        }
+        "departures" | "stops" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            let timetable = options.file("timetable file")?;
+            let format = options.format.unwrap_or(Format::Timetable);
+            let date = options.date_or_today();
+            if command == "departures" {
+                Ok(Command::Departures {
+                    timetable,
+                    format,
+                    date,
+                })
+            } else {
+                Ok(Command::Stops {
+                    timetable,
+                    format,
+                    date,
+                })
+            }
+        }
+        "next" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--until/--stay", has_window),
+                    ("--transfers", options.transfers.is_some()),
+                    ("--pattern/--as", has_sheet),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            if options.origins.is_empty() {
+                return Err("`next` needs a --from stop".into());
+            }
+            let at = match (options.date, options.time) {
+                (None, None) => chrono::Local::now().naive_local(),
+                (_, time) => options
+                    .date_or_today()
+                    .and_time(time.unwrap_or(chrono::NaiveTime::MIN)),
+            };
+            Ok(Command::Next {
+                timetable: options.file("timetable file")?,
+                from: std::mem::take(&mut options.origins),
+                to: std::mem::take(&mut options.destinations),
+                direction: options.direction,
+                at,
+                count: options.count.unwrap_or(5),
+            })
+        }
+        "patterns" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            Ok(Command::Patterns {
+                timetable: options.file("timetable file")?,
+                date: options.date_or_today(),
+            })
+        }
+        "plan" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--count", options.count.is_some()),
+                    ("--until/--stay", has_window),
+                    ("--direction", options.direction.is_some()),
+                    ("--pattern/--as", has_sheet),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            if options.origins.is_empty() || options.destinations.is_empty() {
+                return Err("`plan` needs both --from and --to".into());
+            }
+            let at = match (options.date, options.time) {
+                (None, None) => chrono::Local::now().naive_local(),
+                (_, time) => options
+                    .date_or_today()
+                    .and_time(time.unwrap_or(chrono::NaiveTime::MIN)),
+            };
+            Ok(Command::Plan {
+                timetable: options.file("timetable file")?,
+                from: std::mem::take(&mut options.origins),
+                to: std::mem::take(&mut options.destinations),
+                at,
+                max_transfers: options.transfers.unwrap_or(1),
+            })
+        }
+        "round-trip" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--count", options.count.is_some()),
+                    ("--transfers", options.transfers.is_some()),
+                    ("--direction", options.direction.is_some()),
+                    ("--pattern/--as", has_sheet),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            if options.origins.is_empty() || options.destinations.is_empty() {
+                return Err("`round-trip` needs both --from and --to".into());
+            }
+            let date = options.date_or_today();
+            let until = options
+                .until
+                .unwrap_or(chrono::NaiveTime::from_hms_opt(23, 59, 0).unwrap());
+            Ok(Command::RoundTrip {
+                timetable: options.file("timetable file")?,
+                from: std::mem::take(&mut options.origins),
+                to: std::mem::take(&mut options.destinations),
+                leave_after: date.and_time(options.time.unwrap_or(chrono::NaiveTime::MIN)),
+                leave_before: date.and_time(until),
+                min_stay: chrono::Duration::minutes(options.stay.unwrap_or(0)),
+            })
+        }
+        "search" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--date", has_date),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            if options.positional.len() < 2 {
+                return Err("`search` needs a timetable file and a stop name".into());
+            }
+            let timetable = options.positional.remove(0);
+            Ok(Command::Search {
+                timetable,
+                query: options.positional.join(" "),
+            })
+        }
+        "sheet" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--to", !options.destinations.is_empty()),
+                    ("--format", has_format),
+                    (
+                        "--time/--count",
+                        options.time.is_some() || options.count.is_some(),
+                    ),
+                    ("--until/--stay", has_window),
+                    ("--transfers", options.transfers.is_some()),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            if options.origins.is_empty() {
+                return Err("`sheet` needs a --from stop".into());
+            }
+            Ok(Command::Sheet {
+                timetable: options.file("timetable file")?,
+                stops: std::mem::take(&mut options.origins),
+                from: options.date_or_today(),
+                direction: options.direction,
+                pattern: options.pattern.take(),
+                format: options.sheet_format.unwrap_or(SheetFormat::Text),
+                output: options.output.take(),
+            })
+        }
+        "stations" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("--format", has_format),
+                    ("--date", has_date),
+                    ("query options", has_query),
+                    ("--feed", has_feed),
+                    ("name options", has_names),
+                ],
+            )?;
+            Ok(Command::Stations {
+                timetable: options.file("timetable file")?,
+            })
+        }
+        "validate" => {
+            options.reject(
+                &command,
+                &[
+                    ("route options", has_routes),
+                    ("--from/--to", has_stops),
+                    ("--output", has_output),
+                    ("--date", has_date),
+                    ("query options", has_query),
+                    ("name options", has_names),
+                ],
+            )?;
+            Ok(Command::Validate {
+                timetable: options.file("timetable file")?,
+                format: options.format.unwrap_or(Format::Timetable),
+                feed: options.feed.take(),
+            })
+        }
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        _ => Err(format!("unknown command `{command}`")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> Result<Command, String> {
+        parse(line.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn reject_names_the_first_option_set() {
+        let options = Options::default();
+        assert_eq!(
+            options.reject("next", &[("--output", false), ("--format", false)]),
+            Ok(())
+        );
+        assert_eq!(
+            options.reject(
+                "next",
+                &[("--output", false), ("--format", true), ("--feed", true)]
+            ),
+            Err("`next` does not take --format".to_owned())
+        );
+    }
+
+    #[test]
+    fn help_is_taken_after_any_subcommand() {
+        for line in [
+            "",
+            "help",
+            "--help",
+            "extract --help",
+            "sheet timetable.ron -h",
+            "plan timetable.ron --from A --output x --help",
+        ] {
+            assert!(
+                matches!(parse_line(line), Ok(Command::Help)),
+                "`{line}` does not print the usage"
+            );
+        }
+    }
+
+    #[test]
+    fn commands_reject_the_options_they_do_not_take() {
+        for (line, error) in [
+            (
+                "inspect feed.zip --output x",
+                "`inspect` does not take --output",
+            ),
+            (
+                "departures tt.ron --route R",
+                "`departures` does not take route options",
+            ),
+            ("sheet tt.ron --from A --to B", "`sheet` does not take --to"),
+            (
+                "plan tt.ron --from A --to B --direction inbound",
+                "`plan` does not take --direction",
+            ),
+            (
+                "next tt.ron --from A --stay 10",
+                "`next` does not take --until/--stay",
+            ),
+            (
+                "extract feed.zip -r R --date 2026-10-05",
+                "`extract` does not take --date",
+            ),
+            (
+                "extract feed.zip -r R --format model --names longest",
+                "name options do not apply to `--format model`",
+            ),
+            (
+                "extract feed.zip -r R --from A --to B",
+                "--from and --to cannot be combined with route options",
+            ),
+            (
+                "extract feed.zip --from A",
+                "`extract` needs both --from and --to",
+            ),
+            (
+                "extract feed.zip",
+                "`extract` needs at least one --route, --line or filter",
+            ),
+            ("next tt.ron", "`next` needs a --from stop"),
+            ("stations a.ron b.ron", "unexpected argument `b.ron`"),
+            ("stops --bogus", "unknown option `--bogus`"),
+            ("parse", "unknown command `parse`"),
+        ] {
+            assert_eq!(parse_line(line).unwrap_err(), error, "for `{line}`");
+        }
+    }
+
+    #[test]
+    fn extract_defaults_to_the_timetable_format() {
+        let Ok(Command::Extract {
+            feed,
+            selection: Selection::Routes { selectors, all },
+            output,
+            format,
+            ..
+        }) = parse_line("extract feed.zip -l 42 --mode bus")
+        else {
+            panic!("not a route extraction");
+        };
+        assert_eq!(
+            (feed.as_str(), output.as_str(), format, all),
+            ("feed.zip", "timetable.ron", Format::Timetable, false)
+        );
+        assert_eq!(selectors.len(), 1);
+        assert_eq!(selectors[0].short_name.as_deref(), Some("42"));
+        assert!(selectors[0].route_type.is_some());
+    }
+}
@@ -0,0 +1,598 @@
+use crate::cli::{Format, Naming, Selection};
+use crate::extractor::{self, Extraction, GtfsExtract};
+use crate::route_selector::{describe_route, resolve_route_ids, RouteSelector};
+use crate::timetable::departures::DepartureQuery;
+use crate::timetable::display_names::DisplayRules;
+use crate::timetable::journey_planner::JourneyQuery;
+use crate::timetable::name_rules::NameRules;
+use crate::timetable::round_trip::RoundTripQuery;
+use crate::timetable::stop_sheet::{SheetFormat, SheetQuery};
+use crate::timetable::{Direction, Timetable};
+use chrono::prelude::*;
+use spinoff::{spinners, Spinner};
+use std::collections::HashSet;
+
+fn read_gtfs(feed: &str) -> Result<gtfs_structures::Gtfs, Box<dyn std::error::Error>> {
+    let mut spinner = Spinner::new(spinners::Dots, format!("Parsing GTFS of: {feed}"), None);
+    match gtfs_structures::Gtfs::new(feed) {
+        Ok(gtfs) => {
+            spinner.success("Parsing complete");
+            Ok(gtfs)
+        }
+        Err(error) => {
+            spinner.fail(&error.to_string());
+            Err(error.into())
+        }
+    }
+}
+
+fn read_model(file_name: &str) -> Result<morningstar_model::TimeTable, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(file_name)?;
+    Ok(ron::de::from_reader(file)?)
+}
+
+fn write_model(
+    tt: &morningstar_model::TimeTable,
+    file_name: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // TODO: this should be part of the model?
+    let serialized = ron::ser::to_string(tt)?;
+    let mut file = std::fs::File::create(file_name)?;
+    std::io::Write::write_all(&mut file, serialized.as_bytes())?;
+    Ok(())
+}
+
+fn read_timetable(file_name: &str) -> Result<Timetable, Box<dyn std::error::Error>> {
+    let mut spinner = Spinner::new(spinners::Dots, format!("Reading file {file_name}"), None);
+    let buf = std::fs::read_to_string(file_name)?;
+    spinner.success("Done reading");
+    let mut timetable: Timetable = ron::from_str(&buf)?;
+    // INFO: files extracted before the service calendar existed don't have
+    // it, the sheet needs its dates.
+    if timetable.service_calendar.start_date.is_none() {
+        timetable.build_service_calendar();
+    }
+    // INFO: files extracted before stations existed don't have them.
+    if timetable.stations.is_empty() {
+        timetable.build_stations();
+    }
+    Ok(timetable)
+}
+
+/// Runs an extraction behind a spinner that reports what was extracted.
+fn report_extraction(
+    extract: impl FnOnce() -> Result<extractor::ExtractReport, Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut spinner = Spinner::new(spinners::Dots, "Extracting", None);
+    match extract() {
+        Ok(report) => {
+            spinner.success(&format!("Done extracting: {report}"));
+            Ok(())
+        }
+        Err(error) => {
+            spinner.fail(&error.to_string());
+            Err(error)
+        }
+    }
+}
+
+pub fn extract(
+    feed: &str,
+    selection: &Selection,
+    output: &str,
+    format: Format,
+    naming: &Naming,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let gtfs = read_gtfs(feed)?;
+    match (selection, format) {
+        (Selection::Routes { selectors, all }, Format::Model) => {
+            let routes = resolve_route_ids(&gtfs, selectors, *all)?;
+            let mut extraction = Extraction::new();
+            report_extraction(|| extraction.extract_gtfs_routes(&gtfs, &routes))?;
+            write_model(&extraction.timetable, output)?;
+        }
+        (
+            Selection::StopPairs {
+                origins,
+                destinations,
+            },
+            Format::Model,
+        ) => {
+            let origins = resolve_stop_ids(&gtfs, origins)?;
+            let destinations = resolve_stop_ids(&gtfs, destinations)?;
+            let mut extraction = Extraction::new();
+            report_extraction(|| {
+                extraction.extract_gtfs_stop_pairs(&gtfs, &origins, &destinations)
+            })?;
+            write_model(&extraction.timetable, output)?;
+        }
+        (selection, Format::Timetable) => {
+            let mut tt = Timetable::new();
+            match selection {
+                Selection::Routes { selectors, all } => {
+                    let routes = resolve_route_ids(&gtfs, selectors, *all)?;
+                    report_extraction(|| tt.gtfs_extract(&gtfs, &routes))?;
+                }
+                Selection::StopPairs {
+                    origins,
+                    destinations,
+                } => {
+                    let origins = resolve_stop_ids(&gtfs, origins)?;
+                    let destinations = resolve_stop_ids(&gtfs, destinations)?;
+                    report_extraction(|| {
+                        tt.gtfs_extract_stop_pairs(&gtfs, &origins, &destinations)
+                    })?;
+                }
+            }
+            let mut rules = NameRules::default();
+            if let Some(policies) = &naming.policies {
+                rules.policies.clone_from(policies);
+            }
+            if let Some(overrides) = &naming.overrides {
+                rules.load_overrides(overrides)?;
+            }
+            let renames = tt.uniformise_stop_names(&rules);
+            println!("uniformised the names of {} stops", renames.len());
+            if let Some(report) = &naming.report {
+                let mut lines = vec!["stop_id\tfrom\tto\treason".to_owned()];
+                lines.extend(renames.iter().map(|rename| rename.to_string()));
+                std::fs::write(report, lines.join("\n") + "\n")?;
+                println!("rename report written to {report}");
+            }
+            let mut display_rules = match &naming.display_rules {
+                Some(file_name) => DisplayRules::from_file(file_name)?,
+                None => DisplayRules::default(),
+            };
+            if naming.max_length.is_some() {
+                display_rules.max_length = naming.max_length;
+            }
+            tt.build_display_names(&display_rules);
+            let mut spinner = Spinner::new(spinners::Dots, "Serializing", None);
+            if let Err(error) = tt.to_file(output) {
+                spinner.fail("Serialisation failed");
+                return Err(error);
+            }
+            spinner.success("Done serialising");
+        }
+    }
+    println!("timetable written to {output}");
+    Ok(())
+}
+
+/// Resolves stops given by id, station id or name the way the timetable
+/// format does, see `Timetable::resolve_stops`.
+fn resolve_stop_ids(
+    gtfs: &gtfs_structures::Gtfs,
+    queries: &[String],
+) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+    Ok(Timetable::stop_index(gtfs).resolve_stops(queries)?)
+}
+
+pub fn inspect(feed: &str, routes: &[RouteSelector]) -> Result<(), Box<dyn std::error::Error>> {
+    let gtfs = read_gtfs(feed)?;
+    println!(
+        "{} agencies, {} routes, {} trips, {} stops",
+        gtfs.agencies.len(),
+        gtfs.routes.len(),
+        gtfs.trips.len(),
+        gtfs.stops.len()
+    );
+    for agency in &gtfs.agencies {
+        println!(
+            "agency {}: {}",
+            agency.id.as_deref().unwrap_or("-"),
+            agency.name
+        );
+    }
+    let mut matching: Vec<_> = gtfs
+        .routes
+        .values()
+        .filter(|route| routes.is_empty() || routes.iter().any(|sel| sel.matches(&gtfs, route)))
+        .collect();
+    matching.sort_by(|a, b| (&a.agency_id, &a.short_name).cmp(&(&b.agency_id, &b.short_name)));
+    for route in matching {
+        println!("{}", describe_route(route));
+    }
+    Ok(())
+}
+
+pub fn departures(
+    file_name: &str,
+    format: Format,
+    date: NaiveDate,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        Format::Model => {
+            let tt = read_model(file_name)?;
+            let mut journeys: Vec<_> = tt
+                .get_journeys_for_day(&date)
+                .filter(|journey| !journey.stops.is_empty())
+                .collect();
+            journeys.sort_by(|a, b| a.stops[0].time.cmp(&b.stops[0].time));
+            for journey in journeys {
+                let (Some(first), Some(last)) = (journey.stops.first(), journey.stops.last())
+                else {
+                    continue;
+                };
+                println!(
+                    "{:02}:{:02} {} -> {} ({})",
+                    first.time.hour(),
+                    first.time.minute(),
+                    first.stop_name,
+                    last.stop_name,
+                    journey.service_id
+                );
+            }
+        }
+        Format::Timetable => read_timetable(file_name)?.print_running_on(date),
+    }
+    Ok(())
+}
+
+pub fn next(
+    file_name: &str,
+    from: &[String],
+    to: &[String],
+    direction: Option<Direction>,
+    at: NaiveDateTime,
+    count: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tt = read_timetable(file_name)?;
+    let query = DepartureQuery {
+        from: tt.resolve_stops(from)?,
+        towards: if to.is_empty() {
+            None
+        } else {
+            Some(tt.resolve_stops(to)?)
+        },
+        direction,
+        at,
+        count,
+    };
+    let departures = tt.next_departures(&query);
+    if departures.is_empty() {
+        println!("No departure in the next days");
+    }
+    for departure in departures {
+        print!(
+            "{} (in {} min)\t{}\t{}\ttowards {}",
+            departure.departs_at.format("%Y-%m-%d %H:%M"),
+            departure.wait.num_minutes(),
+            departure.stop_id,
+            departure
+                .trip
+                .short_name
+                .as_deref()
+                .unwrap_or(&departure.trip.route_id),
+            departure.headsign,
+        );
+        match departure.arrives_at {
+            Some(arrives_at) => println!("\tarrives {}", arrives_at.format("%H:%M")),
+            None => println!(),
+        }
+    }
+    Ok(())
+}
+
+pub fn plan(
+    file_name: &str,
+    from: &[String],
+    to: &[String],
+    at: NaiveDateTime,
+    max_transfers: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tt = read_timetable(file_name)?;
+    let query = JourneyQuery {
+        from: tt.resolve_stops(from)?,
+        to: tt.resolve_stops(to)?,
+        at,
+        max_transfers,
+    };
+    let itineraries = tt.plan_journeys(&query);
+    if itineraries.is_empty() {
+        println!("No journey found");
+    }
+    for itinerary in itineraries {
+        let (Some(departs_at), Some(arrives_at)) = (itinerary.departs_at(), itinerary.arrives_at())
+        else {
+            continue;
+        };
+        println!(
+            "{} -> {}, {} transfer(s)",
+            departs_at.format("%Y-%m-%d %H:%M"),
+            arrives_at.format("%H:%M"),
+            itinerary.transfers(),
+        );
+        for leg in &itinerary.legs {
+            println!(
+                "\t{} {}\t-> {} {}\t({})",
+                leg.departs_at().format("%H:%M"),
+                tt.display_name(&leg.from().stop_id)
+                    .unwrap_or(&leg.from().name),
+                leg.arrives_at().format("%H:%M"),
+                tt.display_name(&leg.to().stop_id).unwrap_or(&leg.to().name),
+                leg.trip.route_id,
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn round_trip(
+    file_name: &str,
+    from: &[String],
+    to: &[String],
+    window: std::ops::RangeInclusive<NaiveDateTime>,
+    min_stay: chrono::Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let tt = read_timetable(file_name)?;
+    let query = RoundTripQuery {
+        origin: tt.resolve_stops(from)?,
+        destination: tt.resolve_stops(to)?,
+        leave_after: *window.start(),
+        leave_before: *window.end(),
+        min_stay,
+    };
+    let round_trips = tt.round_trips(&query);
+    if round_trips.is_empty() {
+        println!("No round trip in this window");
+    }
+    for round_trip in round_trips {
+        let arrives_at = round_trip
+            .outbound
+            .arrives_at
+            .map_or("?".into(), |time| time.format("%H:%M").to_string());
+        let comes_back_at = round_trip
+            .inbound
+            .arrives_at
+            .map_or("?".into(), |time| time.format("%H:%M").to_string());
+        println!(
+            "{} -> {}\tstay {}h{:02}\t{} -> {}{}",
+            round_trip.outbound.departs_at.format("%H:%M"),
+            arrives_at,
+            round_trip.stay.num_hours(),
+            round_trip.stay.num_minutes() % 60,
+            round_trip.inbound.departs_at.format("%H:%M"),
+            comes_back_at,
+            if round_trip.is_last_return {
+                "\tlast return"
+            } else {
+                ""
+            },
+        );
+    }
+    Ok(())
+}
+
+pub fn patterns(file_name: &str, date: NaiveDate) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tt = read_timetable(file_name)?;
+    // INFO: timetables extracted before patterns existed don't have them.
+    if tt.route_patterns.is_empty() {
+        tt.build_route_patterns();
+    }
+    let trips = tt.trips_on(date);
+    for pattern in &tt.route_patterns {
+        let direction = pattern
+            .direction
+            .map_or(String::new(), |direction| format!("{direction}, "));
+        println!(
+            "{}\t{}\t{} ({}{} trips)",
+            pattern.id,
+            pattern.route_id,
+            pattern.label,
+            direction,
+            pattern.trip_count(),
+        );
+        let times: Vec<_> = trips
+            .iter()
+            .filter(|(_, trip)| pattern.trip_ids.contains(&trip.id))
+            .map(|(leaves_at, _)| leaves_at.format("%H:%M").to_string())
+            .collect();
+        if !times.is_empty() {
+            println!("\t{}", times.join(" "));
+        }
+    }
+    Ok(())
+}
+
+pub fn sheet(
+    file_name: &str,
+    stops: &[String],
+    from: NaiveDate,
+    direction: Option<Direction>,
+    pattern: Option<String>,
+    format: SheetFormat,
+    output: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tt = read_timetable(file_name)?;
+    if tt.route_patterns.is_empty() {
+        tt.build_route_patterns();
+    }
+    if let Some(pattern) = &pattern {
+        if !tt.route_patterns.iter().any(|known| known.id == *pattern) {
+            return Err(format!("no route pattern with id {pattern}").into());
+        }
+    }
+    let query = SheetQuery {
+        stops: tt.resolve_stops(stops)?,
+        from,
+        direction,
+        pattern_id: pattern,
+    };
+    let sheet = tt.stop_sheet(&query).render(format);
+    match output {
+        Some(output) => std::fs::write(output, sheet)?,
+        None => print!("{sheet}"),
+    }
+    Ok(())
+}
+
+pub fn search(file_name: &str, query: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tt = read_timetable(file_name)?;
+    let matches = tt.search_stops(query);
+    if matches.is_empty() {
+        println!("No stop matches {query}");
+    }
+    for found in matches {
+        let platform = tt
+            .stops
+            .get(found.stop_id)
+            .and_then(|stop| stop.platform_code.as_deref())
+            .unwrap_or("-");
+        println!(
+            "{}\t{}\t{}\t{:?}",
+            found.stop_id, found.name, platform, found.quality
+        );
+    }
+    Ok(())
+}
+
+pub fn stations(file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let tt = read_timetable(file_name)?;
+    for station in &tt.stations {
+        let platforms: Vec<_> = station
+            .stop_ids
+            .iter()
+            .map(|stop_id| {
+                let platform = tt
+                    .stops
+                    .get(stop_id)
+                    .and_then(|stop| stop.platform_code.as_deref())
+                    .unwrap_or("-");
+                format!("{stop_id} ({platform})")
+            })
+            .collect();
+        println!(
+            "{}\t{}\t{} stops\t{}",
+            station.id,
+            station.name,
+            station.stop_ids.len(),
+            platforms.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub fn stops(
+    file_name: &str,
+    format: Format,
+    date: NaiveDate,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        Format::Model => {
+            let tt = read_model(file_name)?;
+            for stop in tt.get_stops_served_on_day(&date) {
+                println!("{stop}");
+            }
+        }
+        Format::Timetable => {
+            let tt = read_timetable(file_name)?;
+            for stop in tt.served_stops_on(date) {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    stop.stop_id,
+                    stop.name,
+                    stop.platform_code.as_deref().unwrap_or("-"),
+                    stop.parent_station.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn validate(
+    file_name: &str,
+    format: Format,
+    feed: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let gtfs = feed.map(read_gtfs).transpose()?;
+    let issues = match format {
+        Format::Model => {
+            let tt = read_model(file_name)?;
+            let mut issues = validate_model(&tt);
+            if let Some(gtfs) = &gtfs {
+                issues.extend(missing_exceptions(&tt, gtfs));
+            }
+            issues
+        }
+        Format::Timetable => {
+            let tt = read_timetable(file_name)?;
+            let mut issues = tt.validate();
+            if let Some(gtfs) = &gtfs {
+                issues.extend(tt.missing_calendar_dates(gtfs));
+            }
+            issues
+        }
+    };
+    if issues.is_empty() {
+        println!("{file_name}: no issue found");
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{issue}");
+    }
+    Err(format!("{file_name}: {} issue(s) found", issues.len()).into())
+}
+
+fn validate_model(tt: &morningstar_model::TimeTable) -> Vec<String> {
+    let mut issues = vec![];
+    if tt.journeys.is_empty() {
+        issues.push("timetable has no journey".to_owned());
+    }
+    for (index, journey) in tt.journeys.iter().enumerate() {
+        if !tt.service_patterns.contains_key(&journey.service_id)
+            && !tt.excpetions.contains_key(&journey.service_id)
+        {
+            issues.push(format!(
+                "journey #{index}: service {} has neither a pattern nor exceptions",
+                journey.service_id
+            ));
+        }
+        if journey
+            .stops
+            .windows(2)
+            .any(|pair| pair[0].time > pair[1].time)
+        {
+            issues.push(format!("journey #{index}: stop times are not in order"));
+        }
+    }
+    issues
+}
+
+/// Compares the exceptions of every service used by the journeys with the
+/// calendar dates of the `gtfs` they were extracted from, returns one line per
+/// date that did not make it.
+fn missing_exceptions(
+    tt: &morningstar_model::TimeTable,
+    gtfs: &gtfs_structures::Gtfs,
+) -> Vec<String> {
+    let service_ids: HashSet<_> = tt
+        .journeys
+        .iter()
+        .map(|journey| journey.service_id.as_str())
+        .collect();
+    let mut issues = vec![];
+    for service_id in service_ids {
+        let Some(calendar_dates) = gtfs.calendar_dates.get(service_id) else {
+            continue;
+        };
+        let stored = tt.excpetions.get_vec(service_id);
+        for calendar_date in calendar_dates {
+            let expected = extractor::exception_convert(calendar_date);
+            let is_stored = stored.is_some_and(|stored| {
+                stored.iter().any(|candidate| {
+                    candidate.date == expected.date
+                        && extractor::same_exception_type(candidate, &expected)
+                })
+            });
+            if !is_stored {
+                issues.push(format!(
+                    "service {service_id}: calendar date {} is missing",
+                    calendar_date.date
+                ));
+            }
+        }
+    }
+    issues
+}
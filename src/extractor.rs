@@ -1,65 +1,321 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+/// What an extraction brought into the timetable.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ExtractReport {
+    pub journeys: usize,
+    /// Services seen for the first time, each one is only processed once.
+    pub services: usize,
+    pub patterns: usize,
+    /// Services that only have calendar dates, no weekly pattern.
+    pub dates_only_services: usize,
+    pub exceptions: usize,
+    /// Journeys left out for being in the timetable already.
+    pub duplicates: usize,
+    /// Journeys already there that a longer part of the same trip replaced.
+    pub replaced: usize,
+}
+
+impl std::fmt::Display for ExtractReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} journeys ({} already there, {} replacing shorter ones), {} new services \
+            ({} by dates only), {} patterns, {} exceptions",
+            self.journeys,
+            self.duplicates,
+            self.replaced,
+            self.services,
+            self.dates_only_services,
+            self.patterns,
+            self.exceptions
+        )
+    }
+}
+
 pub trait GtfsExtract {
-    fn extract_gtfs_route(
+    /// Extracts the journeys of every route matching `predicate`, along with
+    /// their service patterns and exceptions. Data already present is kept,
+    /// so extraction can be called several times to merge routes.
+    fn extract_gtfs_routes_by<P>(
         &mut self,
-        gtfs: gtfs_structures::Gtfs,
-        route_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+        gtfs: &gtfs_structures::Gtfs,
+        predicate: P,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>>
+    where
+        P: Fn(&gtfs_structures::Route) -> bool;
+
+    fn extract_gtfs_routes(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        route_ids: &[String],
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>> {
+        if let Some(route_id) = route_ids.iter().find(|id| !gtfs.routes.contains_key(*id)) {
+            return Err(format!("no route with id {route_id} in the gtfs").into());
+        }
+        self.extract_gtfs_routes_by(gtfs, |route| route_ids.contains(&route.id))
+    }
+
+    /// Extracts every trip that serves one of the `origins` and then one of
+    /// the `destinations`, whatever its route, trimmed to that segment. Stops
+    /// are given by id, a station id stands for all of its stops.
+    fn extract_gtfs_stop_pairs(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        origins: &HashSet<String>,
+        destinations: &HashSet<String>,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>>;
+}
+
+/// A model timetable being extracted into. The model keeps no trip id, so
+/// the trips each journey was taken from are kept alongside, for later
+/// extractions to tell which journeys are there already.
+pub struct Extraction {
+    pub timetable: morningstar_model::TimeTable,
+    /// Parts of the trips extracted so far, by trip id.
+    segments: HashMap<String, Vec<Segment>>,
+}
+
+/// The stop times of a trip a journey was made of.
+struct Segment {
+    stop_times: RangeInclusive<usize>,
+    journey: JourneyKey,
 }
 
-impl GtfsExtract for morningstar_model::TimeTable {
-    fn extract_gtfs_route(
+/// A journey along with the part of the trip it was made of.
+struct ExtractedJourney {
+    trip_id: String,
+    stop_times: RangeInclusive<usize>,
+    journey: morningstar_model::Journey,
+}
+
+impl Extraction {
+    pub fn new() -> Self {
+        Self {
+            timetable: morningstar_model::TimeTable::new(),
+            segments: HashMap::new(),
+        }
+    }
+}
+
+impl GtfsExtract for Extraction {
+    fn extract_gtfs_routes_by<P>(
         &mut self,
-        gtfs: gtfs_structures::Gtfs,
-        route_id: &str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        gtfs: &gtfs_structures::Gtfs,
+        predicate: P,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>>
+    where
+        P: Fn(&gtfs_structures::Route) -> bool,
+    {
+        let route_ids: HashSet<_> = gtfs
+            .routes
+            .values()
+            .filter(|route| predicate(route))
+            .map(|route| route.id.as_str())
+            .collect();
+        if route_ids.is_empty() {
+            return Err("no route matched".into());
+        }
         let journeys: Vec<_> = gtfs
             .trips
             .iter()
-            .filter(|(_, candidate_trip)| candidate_trip.route_id == route_id)
+            .filter(|(_, candidate_trip)| route_ids.contains(candidate_trip.route_id.as_str()))
             .map(|(_, value)| value)
             .filter_map(trip_convert)
             .collect();
+        self.add_journeys(gtfs, journeys)
+    }
+
+    fn extract_gtfs_stop_pairs(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        origins: &HashSet<String>,
+        destinations: &HashSet<String>,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>> {
+        let journeys: Vec<_> = gtfs
+            .trips
+            .values()
+            .filter_map(|trip| trip_segment_convert(trip, origins, destinations))
+            .collect();
+        self.add_journeys(gtfs, journeys)
+    }
+}
+
+impl Extraction {
+    fn add_journeys(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        journeys: Vec<ExtractedJourney>,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>> {
         if journeys.is_empty() {
             return Err("no trip was available".into());
         }
-        // let services: std::collections::HashSet<_> =
-        journeys
+        let mut report = ExtractReport::default();
+        // INFO: extractions overlap when merging routes, or routes and stop
+        // pairs. A journey within a part of its trip already extracted would
+        // run twice a day, and a longer one takes the place of the parts it
+        // covers.
+        let mut added = vec![];
+        for extracted in journeys {
+            let segments = self.segments.entry(extracted.trip_id).or_default();
+            if segments
+                .iter()
+                .any(|segment| covers(&segment.stop_times, &extracted.stop_times))
+            {
+                report.duplicates += 1;
+                continue;
+            }
+            let (covered, kept): (Vec<_>, Vec<_>) = std::mem::take(segments)
+                .into_iter()
+                .partition(|segment| covers(&extracted.stop_times, &segment.stop_times));
+            *segments = kept;
+            for segment in covered {
+                let position = self
+                    .timetable
+                    .journeys
+                    .iter()
+                    .position(|journey| journey_key(journey) == segment.journey);
+                if let Some(position) = position {
+                    self.timetable.journeys.remove(position);
+                    report.replaced += 1;
+                }
+            }
+            segments.push(Segment {
+                stop_times: extracted.stop_times,
+                journey: journey_key(&extracted.journey),
+            });
+            added.push(extracted.journey);
+        }
+        report.journeys = added.len();
+        // INFO: services brought in by a previous extraction already have
+        // their pattern and exceptions, inserting them again would duplicate
+        // the exceptions.
+        let tt = &mut self.timetable;
+        let known_services: HashSet<_> = tt
+            .service_patterns
+            .keys()
+            .chain(tt.excpetions.keys())
+            .cloned()
+            .collect();
+        let services: HashSet<_> = added
             .iter()
             .map(|journey| journey.service_id.clone())
-            .for_each(|service_id| extract_pattern_and_exceptions(self, &gtfs, service_id));
-        // .collect();
-        // for service_id in services {
-        //     extract_pattern_and_exceptions(self, &gtfs, service_id);
-        // }
-        self.journeys = journeys;
-        self.sort_journeys_and_stops();
-        Ok(())
+            .filter(|service_id| !known_services.contains(service_id))
+            .collect();
+        for service_id in services {
+            let (has_pattern, exceptions) = extract_pattern_and_exceptions(tt, gtfs, service_id);
+            report.services += 1;
+            report.patterns += usize::from(has_pattern);
+            report.dates_only_services += usize::from(!has_pattern && exceptions > 0);
+            report.exceptions += exceptions;
+        }
+        tt.journeys.extend(added);
+        tt.sort_journeys_and_stops();
+        Ok(report)
     }
 }
 
+fn covers(outer: &RangeInclusive<usize>, inner: &RangeInclusive<usize>) -> bool {
+    outer.start() <= inner.start() && inner.end() <= outer.end()
+}
+
+type JourneyKey = (String, Vec<(chrono::NaiveTime, String)>);
+
+/// Finds the journey a segment was made into: journeys with the same key
+/// can only be told apart by the trip they came from.
+fn journey_key(journey: &morningstar_model::Journey) -> JourneyKey {
+    let stops = journey
+        .stops
+        .iter()
+        .map(|stop| (stop.time, stop.stop_name.clone()))
+        .collect();
+    (journey.service_id.clone(), stops)
+}
+
+/// Returns whether the service has a pattern and how many exceptions it has.
 fn extract_pattern_and_exceptions(
     tt: &mut morningstar_model::TimeTable,
     gtfs: &gtfs_structures::Gtfs,
     service_id: String,
-) {
-    if let Some(calendar) = gtfs.calendar.get(&service_id) {
+) -> (bool, usize) {
+    let callendar_dates = gtfs.calendar_dates.get(&service_id);
+    let has_pattern = if let Some(calendar) = gtfs.calendar.get(&service_id) {
         let pattern = callendar_to_pattern(calendar);
         tt.service_patterns.insert(service_id.clone(), pattern);
+        true
+    } else if let Some(pattern) = callendar_dates.and_then(|dates| dates_only_pattern(dates)) {
+        tt.service_patterns.insert(service_id.clone(), pattern);
+        false
+    } else {
+        false
+    };
+    let Some(callendar_dates) = callendar_dates else {
+        return (has_pattern, 0);
+    };
+    let mut excpetions: Vec<_> = callendar_dates.iter().map(exception_convert).collect();
+    // INFO: some feeds repeat the same calendar_dates row, only one copy
+    // of each is worth keeping. Sorting on the type too puts the copies
+    // next to one another even when a conflicting row sits between them.
+    excpetions.sort_by_key(|excpetion| {
+        let added = matches!(
+            excpetion.exception_type,
+            morningstar_model::Exception::Added
+        );
+        (excpetion.date, added)
+    });
+    excpetions.dedup_by(|a, b| a.date == b.date && same_exception_type(a, b));
+    if excpetions
+        .windows(2)
+        .any(|pair| pair[0].date == pair[1].date)
+    {
+        eprintln!("warning: conflicting exceptions detected (service_id: {service_id})");
     }
-    if let Some(callendar_dates) = gtfs.calendar_dates.get(&service_id) {
-        for callendar_date in callendar_dates {
-            let excpetion = morningstar_model::ServiceException {
-                date: callendar_date.date,
-                exception_type: match callendar_date.exception_type {
-                    gtfs_structures::Exception::Added => morningstar_model::Exception::Added,
-                    gtfs_structures::Exception::Deleted => morningstar_model::Exception::Deleted,
-                },
-            };
-            tt.excpetions.insert(service_id.clone(), excpetion);
-        }
+    // INFO: a service is only processed once, its exceptions are stored as
+    // one list, in place of whatever the multimap held for it.
+    let count = excpetions.len();
+    *tt.excpetions.entry(service_id).or_insert_vec(vec![]) = excpetions;
+    (has_pattern, count)
+}
+
+pub fn exception_convert(
+    callendar_date: &gtfs_structures::CalendarDate,
+) -> morningstar_model::ServiceException {
+    morningstar_model::ServiceException {
+        date: callendar_date.date,
+        exception_type: match callendar_date.exception_type {
+            gtfs_structures::Exception::Added => morningstar_model::Exception::Added,
+            gtfs_structures::Exception::Deleted => morningstar_model::Exception::Deleted,
+        },
     }
 }
 
+pub fn same_exception_type(
+    a: &morningstar_model::ServiceException,
+    b: &morningstar_model::ServiceException,
+) -> bool {
+    use morningstar_model::Exception;
+    matches!(
+        (&a.exception_type, &b.exception_type),
+        (Exception::Added, Exception::Added) | (Exception::Deleted, Exception::Deleted)
+    )
+}
+
+/// Services can be defined by calendar_dates.txt alone. Those get a pattern
+/// that never runs on its own and spans all of their dates, so that they run
+/// on their added dates, and only on them.
+fn dates_only_pattern(
+    callendar_dates: &[gtfs_structures::CalendarDate],
+) -> Option<morningstar_model::ServicePattern> {
+    let start_date = callendar_dates.iter().map(|date| date.date).min()?;
+    let end_date = callendar_dates.iter().map(|date| date.date).max()?;
+    Some(morningstar_model::ServicePattern {
+        weekdays: morningstar_model::WeekdayFlags::NEVER,
+        start_date,
+        end_date,
+    })
+}
+
 fn callendar_to_pattern(calendar: &gtfs_structures::Calendar) -> morningstar_model::ServicePattern {
     use morningstar_model::WeekdayFlags;
     let mut pattern = morningstar_model::ServicePattern {
@@ -91,7 +347,8 @@ fn callendar_to_pattern(calendar: &gtfs_structures::Calendar) -> morningstar_mod
     return pattern;
 }
 
-fn trip_convert(trip: &gtfs_structures::Trip) -> Option<morningstar_model::Journey> {
+fn trip_convert(trip: &gtfs_structures::Trip) -> Option<ExtractedJourney> {
+    warn_if_past_midnight(&trip.id, &trip.stop_times);
     let stops: Vec<_> = trip
         .stop_times
         .iter()
@@ -101,16 +358,105 @@ fn trip_convert(trip: &gtfs_structures::Trip) -> Option<morningstar_model::Journ
     if stops.is_empty() {
         None
     } else {
-        Some(morningstar_model::Journey {
+        Some(ExtractedJourney {
+            trip_id: trip.id.clone(),
+            stop_times: 0..=trip.stop_times.len() - 1,
+            journey: morningstar_model::Journey {
+                service_id: trip.service_id.clone(),
+                stops,
+            },
+        })
+    }
+}
+
+/// Indices in `trip.stop_times` of the part of `trip` that goes from one of
+/// the `origins` to one of the `destinations`, `None` if the trip does not
+/// serve them in that order. Stops are given by id, a station id stands for
+/// all of its stops.
+pub fn trip_segment(
+    trip: &gtfs_structures::Trip,
+    origins: &HashSet<String>,
+    destinations: &HashSet<String>,
+) -> Option<RangeInclusive<usize>> {
+    let is_in = |set: &HashSet<String>, stop: &gtfs_structures::Stop| {
+        set.contains(&stop.id)
+            || stop
+                .parent_station
+                .as_ref()
+                .is_some_and(|parent| set.contains(parent))
+    };
+    let first_origin = trip
+        .stop_times
+        .iter()
+        .position(|stop_time| is_in(origins, &stop_time.stop))?;
+    let destination = first_origin
+        + trip.stop_times[first_origin..]
+            .iter()
+            .position(|stop_time| is_in(destinations, &stop_time.stop))?;
+    // INFO: on loops, the origin may be served again before reaching the
+    // destination, the rider boards at the last occurrence.
+    let origin = trip.stop_times[..destination]
+        .iter()
+        .rposition(|stop_time| is_in(origins, &stop_time.stop))?;
+    Some(origin..=destination)
+}
+
+/// Converts the part of `trip` that goes from one of the `origins` to one of
+/// the `destinations`, see `trip_segment`.
+fn trip_segment_convert(
+    trip: &gtfs_structures::Trip,
+    origins: &HashSet<String>,
+    destinations: &HashSet<String>,
+) -> Option<ExtractedJourney> {
+    let stop_times = trip_segment(trip, origins, destinations)?;
+    let segment = &trip.stop_times[stop_times.clone()];
+    let (first, last) = (segment.first()?, segment.last()?);
+    if segment.len() < 2 {
+        return None;
+    }
+    // INFO: a journey that lost its origin or destination, for being past
+    // 24:00:00 or having no name, no longer serves the pair.
+    if stop_time_convert(first).is_none() || stop_time_convert(last).is_none() {
+        eprintln!(
+            "warning: trip {} is left out, its stop {} or {} is past 24:00:00 or has no name",
+            trip.id, first.stop.id, last.stop.id
+        );
+        return None;
+    }
+    warn_if_past_midnight(&trip.id, segment);
+    let stops: Vec<_> = segment.iter().filter_map(stop_time_convert).collect();
+    Some(ExtractedJourney {
+        trip_id: trip.id.clone(),
+        stop_times,
+        journey: morningstar_model::Journey {
             service_id: trip.service_id.clone(),
             stops,
-        })
+        },
+    })
+}
+
+// INFO: morningstar_model::StopTime holds a chrono::NaiveTime, which can't
+// represent GTFS times past 24:00:00, so those stops are left out. The
+// timetable format keeps them.
+fn warn_if_past_midnight(trip_id: &str, stop_times: &[gtfs_structures::StopTime]) {
+    let past_midnight = stop_times.iter().any(|stop_time| {
+        stop_time
+            .departure_time
+            .or(stop_time.arrival_time)
+            .is_some_and(|seconds| seconds >= 24 * 60 * 60)
+    });
+    if past_midnight {
+        eprintln!(
+            "warning: trip {trip_id} runs past midnight, its stops after 24:00:00 are left out"
+        );
     }
 }
 
+/// The model keeps a single time per stop: the departure time, or the arrival
+/// time where the vehicle does not leave again, such as at the terminus.
 fn stop_time_convert(stop_time: &gtfs_structures::StopTime) -> Option<morningstar_model::StopTime> {
     let stop_name = stop_time.stop.name.clone()?;
-    let seconds_from_midnight = stop_time.departure_time?;
+    let seconds_from_midnight = stop_time.departure_time.or(stop_time.arrival_time)?;
     let time_of_day =
         chrono::NaiveTime::from_num_seconds_from_midnight_opt(seconds_from_midnight, 0)?;
     Some(morningstar_model::StopTime {
@@ -118,3 +464,223 @@ fn stop_time_convert(stop_time: &gtfs_structures::StopTime) -> Option<morningsta
         stop_name: stop_name.clone(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use gtfs_structures::{Calendar, CalendarDate, Exception, Gtfs, Route, Stop, StopTime, Trip};
+    use std::sync::Arc;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, day).unwrap()
+    }
+
+    fn stop_time(name: &str, seconds: u32) -> StopTime {
+        StopTime {
+            stop: Arc::new(Stop {
+                id: name.to_owned(),
+                name: Some(name.to_owned()),
+                ..Default::default()
+            }),
+            departure_time: Some(seconds),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merged_extractions_keep_journeys_once() {
+        let mut gtfs = Gtfs::default();
+        for (route_id, trip_id, hour) in [("R1", "T1", 8), ("R2", "T2", 9)] {
+            gtfs.routes.insert(
+                route_id.to_owned(),
+                Route {
+                    id: route_id.to_owned(),
+                    ..Default::default()
+                },
+            );
+            gtfs.trips.insert(
+                trip_id.to_owned(),
+                Trip {
+                    id: trip_id.to_owned(),
+                    service_id: "S".to_owned(),
+                    route_id: route_id.to_owned(),
+                    stop_times: vec![
+                        stop_time("A", hour * 3600),
+                        stop_time("B", hour * 3600 + 600),
+                    ],
+                    ..Default::default()
+                },
+            );
+        }
+        let mut tt = Extraction::new();
+        tt.extract_gtfs_routes(&gtfs, &["R1".to_owned()]).unwrap();
+        let report = tt
+            .extract_gtfs_routes(&gtfs, &["R1".to_owned(), "R2".to_owned()])
+            .unwrap();
+        assert_eq!((report.journeys, report.duplicates), (1, 1));
+        let stops = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let report = tt
+            .extract_gtfs_stop_pairs(&gtfs, &stops(&["A"]), &stops(&["B"]))
+            .unwrap();
+        assert_eq!((report.journeys, report.duplicates), (0, 2));
+        assert_eq!(tt.timetable.journeys.len(), 2);
+    }
+
+    #[test]
+    fn overlapping_extractions_keep_the_longest_part_of_a_trip() {
+        let mut gtfs = Gtfs::default();
+        gtfs.routes.insert(
+            "R".to_owned(),
+            Route {
+                id: "R".to_owned(),
+                ..Default::default()
+            },
+        );
+        // INFO: two trips with the same service, times and names are still
+        // two vehicles.
+        for trip_id in ["T1", "T2"] {
+            gtfs.trips.insert(
+                trip_id.to_owned(),
+                Trip {
+                    id: trip_id.to_owned(),
+                    service_id: "S".to_owned(),
+                    route_id: "R".to_owned(),
+                    stop_times: ["A", "B", "C", "D"]
+                        .iter()
+                        .zip(0..)
+                        .map(|(name, index)| stop_time(name, 8 * 3600 + index * 600))
+                        .collect(),
+                    ..Default::default()
+                },
+            );
+        }
+        let stops = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let mut tt = Extraction::new();
+        let report = tt
+            .extract_gtfs_stop_pairs(&gtfs, &stops(&["B"]), &stops(&["C"]))
+            .unwrap();
+        assert_eq!((report.journeys, report.duplicates), (2, 0));
+
+        let report = tt.extract_gtfs_routes(&gtfs, &["R".to_owned()]).unwrap();
+        assert_eq!((report.journeys, report.replaced), (2, 2));
+        assert_eq!(tt.timetable.journeys.len(), 2);
+        assert!(tt
+            .timetable
+            .journeys
+            .iter()
+            .all(|journey| journey.stops.len() == 4));
+
+        let report = tt
+            .extract_gtfs_stop_pairs(&gtfs, &stops(&["A"]), &stops(&["C"]))
+            .unwrap();
+        assert_eq!((report.journeys, report.duplicates), (0, 2));
+        assert_eq!(tt.timetable.journeys.len(), 2);
+    }
+
+    #[test]
+    fn segments_past_midnight_are_left_out() {
+        let trip = Trip {
+            id: "T".to_owned(),
+            service_id: "S".to_owned(),
+            stop_times: vec![
+                stop_time("A", 23 * 3600),
+                stop_time("B", 23 * 3600 + 1800),
+                stop_time("C", 24 * 3600 + 600),
+            ],
+            ..Default::default()
+        };
+        let stops = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let extracted = trip_segment_convert(&trip, &stops(&["A"]), &stops(&["B"])).unwrap();
+        assert_eq!(extracted.journey.stops.len(), 2);
+        assert!(trip_segment_convert(&trip, &stops(&["A"]), &stops(&["C"])).is_none());
+    }
+
+    #[test]
+    fn exceptions_survive_a_round_trip() {
+        let mut gtfs = Gtfs::default();
+        gtfs.routes.insert(
+            "R".to_owned(),
+            Route {
+                id: "R".to_owned(),
+                ..Default::default()
+            },
+        );
+        gtfs.trips.insert(
+            "T".to_owned(),
+            Trip {
+                id: "T".to_owned(),
+                service_id: "S".to_owned(),
+                route_id: "R".to_owned(),
+                stop_times: vec![stop_time("A", 8 * 3600), stop_time("B", 9 * 3600)],
+                ..Default::default()
+            },
+        );
+        gtfs.calendar.insert(
+            "S".to_owned(),
+            Calendar {
+                id: "S".to_owned(),
+                monday: true,
+                tuesday: true,
+                wednesday: true,
+                thursday: true,
+                friday: true,
+                saturday: false,
+                sunday: false,
+                start_date: date(1),
+                end_date: date(30),
+            },
+        );
+        let rows = [
+            (3, Exception::Deleted),
+            (8, Exception::Added),
+            (10, Exception::Deleted),
+            // INFO: a duplicate row, and a conflicting pair on the 20th.
+            (10, Exception::Deleted),
+            (20, Exception::Added),
+            (20, Exception::Deleted),
+            (20, Exception::Added),
+            (29, Exception::Added),
+        ];
+        gtfs.calendar_dates.insert(
+            "S".to_owned(),
+            rows.iter()
+                .map(|(day, exception_type)| CalendarDate {
+                    service_id: "S".to_owned(),
+                    date: date(*day),
+                    exception_type: *exception_type,
+                })
+                .collect(),
+        );
+
+        let mut tt = Extraction::new();
+        let report = tt.extract_gtfs_routes(&gtfs, &["R".to_owned()]).unwrap();
+        assert_eq!(report.exceptions, 6);
+        // INFO: extracting the route again leaves the service as it was.
+        let report = tt.extract_gtfs_routes(&gtfs, &["R".to_owned()]).unwrap();
+        assert_eq!((report.services, report.exceptions), (0, 0));
+        assert_eq!(tt.timetable.excpetions.get_vec("S").map(Vec::len), Some(6));
+        let serialized = ron::ser::to_string(&tt.timetable).unwrap();
+        let tt: morningstar_model::TimeTable = ron::from_str(&serialized).unwrap();
+
+        let read_back: Vec<_> = tt
+            .excpetions
+            .get_vec("S")
+            .unwrap()
+            .iter()
+            .map(|exception| {
+                let added = matches!(
+                    exception.exception_type,
+                    morningstar_model::Exception::Added
+                );
+                (exception.date, added)
+            })
+            .collect();
+        for (day, exception_type) in rows {
+            let expected = (date(day), exception_type == Exception::Added);
+            let count = read_back.iter().filter(|pair| **pair == expected).count();
+            assert_eq!(count, 1, "{expected:?} is there {count} times");
+        }
+        assert_eq!(read_back.len(), 6);
+    }
+}
@@ -0,0 +1,195 @@
+use gtfs_structures::{Gtfs, Route, RouteType};
+
+/// Criteria a route has to meet to be selected. Every criterion that is set
+/// must match; an empty selector matches every route.
+#[derive(Debug, Default, Clone)]
+pub struct RouteSelector {
+    /// Exact GTFS route id, such as `IDFM:C02298`.
+    pub id: Option<String>,
+    /// Public line number, such as `2262`, compared case-insensitively.
+    pub short_name: Option<String>,
+    /// Case-insensitive substring of the route long name.
+    pub long_name: Option<String>,
+    /// Agency id or agency name.
+    pub agency: Option<String>,
+    pub route_type: Option<RouteType>,
+}
+
+#[derive(Debug)]
+pub enum SelectionError {
+    NoMatch(String),
+    Ambiguous {
+        selector: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoMatch(selector) => write!(f, "no route matches {selector}"),
+            Self::Ambiguous {
+                selector,
+                candidates,
+            } => {
+                write!(f, "{selector} matches {} routes:", candidates.len())?;
+                for candidate in candidates {
+                    write!(f, "\n    {candidate}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectionError {}
+
+impl std::fmt::Display for RouteSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut criteria = vec![];
+        if let Some(id) = &self.id {
+            criteria.push(format!("id {id}"));
+        }
+        if let Some(short_name) = &self.short_name {
+            criteria.push(format!("line {short_name}"));
+        }
+        if let Some(long_name) = &self.long_name {
+            criteria.push(format!("name \"{long_name}\""));
+        }
+        if let Some(agency) = &self.agency {
+            criteria.push(format!("agency {agency}"));
+        }
+        if let Some(route_type) = &self.route_type {
+            criteria.push(format!("mode {route_type:?}"));
+        }
+        if criteria.is_empty() {
+            write!(f, "any route")
+        } else {
+            write!(f, "{}", criteria.join(", "))
+        }
+    }
+}
+
+impl RouteSelector {
+    pub fn matches(&self, gtfs: &Gtfs, route: &Route) -> bool {
+        if self.id.as_ref().is_some_and(|id| *id != route.id) {
+            return false;
+        }
+        if let Some(short_name) = &self.short_name {
+            let Some(route_short_name) = &route.short_name else {
+                return false;
+            };
+            if !route_short_name.eq_ignore_ascii_case(short_name) {
+                return false;
+            }
+        }
+        if let Some(long_name) = &self.long_name {
+            let Some(route_long_name) = &route.long_name else {
+                return false;
+            };
+            if !route_long_name
+                .to_lowercase()
+                .contains(&long_name.to_lowercase())
+            {
+                return false;
+            }
+        }
+        if let Some(agency) = &self.agency {
+            if !route_agency_matches(gtfs, route, agency) {
+                return false;
+            }
+        }
+        if self
+            .route_type
+            .is_some_and(|route_type| route_type != route.route_type)
+        {
+            return false;
+        }
+        true
+    }
+
+    /// Resolves the selector against the routes of `gtfs`. Unless `allow_many`
+    /// is set, matching more than one route is an error listing the candidates.
+    pub fn resolve<'a>(
+        &self,
+        gtfs: &'a Gtfs,
+        allow_many: bool,
+    ) -> Result<Vec<&'a Route>, SelectionError> {
+        let mut routes: Vec<_> = gtfs
+            .routes
+            .values()
+            .filter(|route| self.matches(gtfs, route))
+            .collect();
+        routes.sort_by(|a, b| a.id.cmp(&b.id));
+        match routes.len() {
+            0 => Err(SelectionError::NoMatch(self.to_string())),
+            1 => Ok(routes),
+            _ if allow_many => Ok(routes),
+            _ => Err(SelectionError::Ambiguous {
+                selector: self.to_string(),
+                candidates: routes.iter().map(|route| describe_route(route)).collect(),
+            }),
+        }
+    }
+}
+
+/// Resolves every selector and returns the ids of the selected routes,
+/// without duplicates.
+pub fn resolve_route_ids(
+    gtfs: &Gtfs,
+    selectors: &[RouteSelector],
+    allow_many: bool,
+) -> Result<Vec<String>, SelectionError> {
+    let mut route_ids: Vec<String> = vec![];
+    for selector in selectors {
+        for route in selector.resolve(gtfs, allow_many)? {
+            if !route_ids.contains(&route.id) {
+                route_ids.push(route.id.clone());
+            }
+        }
+    }
+    Ok(route_ids)
+}
+
+// INFO: agency_id is optional in routes.txt when the feed has a single agency.
+fn route_agency_matches(gtfs: &Gtfs, route: &Route, agency: &str) -> bool {
+    let agency_id = match (&route.agency_id, gtfs.agencies.as_slice()) {
+        (Some(agency_id), _) => Some(agency_id.as_str()),
+        (None, [only_agency]) => only_agency.id.as_deref(),
+        (None, _) => None,
+    };
+    if agency_id == Some(agency) {
+        return true;
+    }
+    gtfs.agencies
+        .iter()
+        .filter(|candidate| candidate.name.eq_ignore_ascii_case(agency))
+        .any(|candidate| candidate.id.as_deref() == agency_id || gtfs.agencies.len() == 1)
+}
+
+pub fn describe_route(route: &Route) -> String {
+    format!(
+        "{}\t{}\t{}\t{}",
+        route.id,
+        route.short_name.as_deref().unwrap_or("-"),
+        route.long_name.as_deref().unwrap_or("-"),
+        route.agency_id.as_deref().unwrap_or("-"),
+    )
+}
+
+pub fn parse_route_type(s: &str) -> Result<RouteType, String> {
+    match s.to_lowercase().as_str() {
+        "tram" | "tramway" => Ok(RouteType::Tramway),
+        "subway" | "metro" => Ok(RouteType::Subway),
+        "rail" | "train" => Ok(RouteType::Rail),
+        "bus" => Ok(RouteType::Bus),
+        "ferry" => Ok(RouteType::Ferry),
+        "cablecar" => Ok(RouteType::CableCar),
+        "gondola" => Ok(RouteType::Gondola),
+        "funicular" => Ok(RouteType::Funicular),
+        "coach" => Ok(RouteType::Coach),
+        "air" => Ok(RouteType::Air),
+        "taxi" => Ok(RouteType::Taxi),
+        _ => Err(format!("unknown mode `{s}`")),
+    }
+}
@@ -1,33 +1,134 @@
+pub mod departures;
+pub mod display_names;
+#[cfg(test)]
+mod fixtures;
 pub mod gtfs_extract;
-pub mod runs_today;
+pub mod journey_planner;
+pub mod name_rules;
+pub mod round_trip;
+pub mod route_patterns;
+pub mod runs_on;
+pub mod service_calendar;
+pub mod service_time;
+pub mod stations;
+pub mod stop_lookup;
+pub mod stop_search;
+pub mod stop_sheet;
+pub mod transfers;
 pub mod uniformise_stop_names;
+pub mod validate;
 
 use multimap::MultiMap;
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use service_time::ServiceTime;
+use std::collections::HashMap;
 
 mod my_gtfs_structs;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Timetable {
-    pub now: chrono::NaiveDateTime,
-    pub today: chrono::NaiveDate,
-    pub current_time: chrono::NaiveTime,
     pub calendar: HashMap<String, my_gtfs_structs::Calendar>,
     pub calendar_dates: HashMap<String, Vec<my_gtfs_structs::CalendarDate>>,
     pub stops: HashMap<String, my_gtfs_structs::Stop>,
     pub routes: HashMap<String, my_gtfs_structs::Route>,
+    /// Shortened stop names by stop id, see `build_display_names`.
+    #[serde(default)]
+    pub display_names: HashMap<String, String>,
     pub trips: MultiMap<String, Trip>,
-    running_services_cache: RefCell<HashSet<String>>,
-    non_running_services_cache: RefCell<HashSet<String>>,
+    /// Trips grouped by the stops they serve, see `build_route_patterns`.
+    #[serde(default)]
+    pub route_patterns: Vec<route_patterns::RoutePattern>,
+    /// Transfers from each stop, see `import_transfers`.
+    #[serde(default)]
+    pub transfers: HashMap<String, Vec<transfers::Transfer>>,
+    /// Stops grouped into the places riders know, see `build_stations`.
+    #[serde(default)]
+    pub stations: Vec<stations::Station>,
+    #[serde(default)]
+    pub service_calendar: service_calendar::ServiceCalendar,
+    #[serde(skip)]
+    service_day_cache: runs_on::ServiceDayCache,
 }
 
+// INFO: a single timetable is meant to be shared between threads, behind an
+// Arc, by the web server and dashboard.
+const _: fn() = || {
+    fn is_shareable<T: Send + Sync>() {}
+    is_shareable::<Timetable>();
+};
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Trip {
     pub id: String,
     pub service_id: String,
     pub route_id: String,
     pub stop_times: Vec<StopTime>,
+    #[serde(default)]
+    pub headsign: Option<String>,
+    /// Name riders know the trip by, such as a train number.
+    #[serde(default)]
+    pub short_name: Option<String>,
+    #[serde(default)]
+    pub direction: Option<Direction>,
+}
+
+/// Which way a trip goes along its route, as `direction_id` in GTFS. What
+/// each way means is up to the feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Direction {
+    Outbound,
+    Inbound,
+}
+
+impl From<gtfs_structures::DirectionType> for Direction {
+    fn from(value: gtfs_structures::DirectionType) -> Self {
+        match value {
+            gtfs_structures::DirectionType::Outbound => Self::Outbound,
+            gtfs_structures::DirectionType::Inbound => Self::Inbound,
+        }
+    }
+}
+
+impl Direction {
+    pub fn opposite(self) -> Self {
+        match self {
+            Self::Outbound => Self::Inbound,
+            Self::Inbound => Self::Outbound,
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Outbound => write!(f, "outbound"),
+            Self::Inbound => write!(f, "inbound"),
+        }
+    }
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "outbound" | "0" => Ok(Self::Outbound),
+            "inbound" | "1" => Ok(Self::Inbound),
+            _ => Err(format!(
+                "unknown direction `{s}`, expected `outbound` or `inbound`"
+            )),
+        }
+    }
+}
+
+impl Trip {
+    /// Where the trip is headed as shown to riders, its last stop when the
+    /// feed doesn't say.
+    pub fn headsign(&self) -> &str {
+        match (&self.headsign, self.stop_times.last()) {
+            (Some(headsign), _) => headsign,
+            (None, Some(last_stop)) => &last_stop.name,
+            (None, None) => "",
+        }
+    }
 }
 
 impl From<&gtfs_structures::Trip> for Trip {
@@ -41,32 +142,43 @@ impl From<&gtfs_structures::Trip> for Trip {
                 .iter()
                 .filter_map(|item| StopTime::try_from(item).ok())
                 .collect(),
+            headsign: value.trip_headsign.clone(),
+            short_name: value.trip_short_name.clone(),
+            direction: value.direction_id.map(Direction::from),
         }
     }
 }
 
+/// Arrival and departure only differ at timing points and termini. Queries
+/// boarding a trip at a stop look at `departure`, queries getting off at a
+/// stop look at `arrival`.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct StopTime {
-    pub time: chrono::NaiveTime,
+    pub arrival: ServiceTime,
+    pub departure: ServiceTime,
     pub stop_id: String,
     pub name: String,
+    pub parent_station: Option<String>,
+    pub platform_code: Option<String>,
 }
 
 impl TryFrom<&gtfs_structures::StopTime> for StopTime {
     type Error = &'static str;
     fn try_from(value: &gtfs_structures::StopTime) -> Result<Self, Self::Error> {
-        let time = chrono::NaiveTime::from_num_seconds_from_midnight_opt(
-            value
-                .departure_time
-                .or(value.arrival_time)
-                .ok_or("no arrival or departure time on stop")?,
-            0,
-        )
-        .ok_or("could not convert arival/departure time to chrono::NaiveTime")?;
+        // INFO: GTFS allows leaving one of the two times blank, in which case
+        // it is the same as the other.
+        let arrival = value
+            .arrival_time
+            .or(value.departure_time)
+            .ok_or("no arrival or departure time on stop")?;
+        let departure = value.departure_time.unwrap_or(arrival);
         Ok(Self {
-            time,
+            arrival: ServiceTime::from_seconds(arrival),
+            departure: ServiceTime::from_seconds(departure),
             stop_id: value.stop.id.clone(),
             name: value.stop.name.clone().ok_or("stop without a name")?,
+            parent_station: value.stop.parent_station.clone(),
+            platform_code: value.stop.platform_code.clone(),
         })
     }
 }
@@ -75,68 +187,85 @@ use chrono::prelude::*;
 
 impl Timetable {
     pub fn new() -> Self {
-        let now = Local::now();
-        // println!("{now:#?}");
-        let now_naive: chrono::NaiveDateTime = now.naive_local();
         Self {
-            now: now_naive,
-            today: now_naive.date(),
-            current_time: now_naive.time(),
             calendar: HashMap::new(),
             calendar_dates: HashMap::new(),
             stops: HashMap::new(),
             routes: HashMap::new(),
+            display_names: HashMap::new(),
             trips: MultiMap::new(),
-            running_services_cache: RefCell::new(HashSet::new()),
-            non_running_services_cache: RefCell::new(HashSet::new()),
+            route_patterns: vec![],
+            transfers: HashMap::new(),
+            stations: vec![],
+            service_calendar: Default::default(),
+            service_day_cache: Default::default(),
         }
     }
 
-    pub fn print_running_today(&self) {
+    /// Trips leaving their first stop on `date`, along with the date and
+    /// time they leave at, in chronological order. This includes the trips of
+    /// the previous service day that leave after midnight.
+    pub fn trips_on(&self, date: NaiveDate) -> Vec<(NaiveDateTime, &Trip)> {
+        let Some(day_before) = date.pred_opt() else {
+            return vec![];
+        };
         let mut trips: Vec<_> = self
             .trips
             .iter()
-            .map(|(_, b)| b)
-            .filter(|trip| self.runs_today(&trip.service_id))
+            .filter_map(|(_, trip)| Some((trip.stop_times.first()?.departure, trip)))
+            .flat_map(|(departure, trip)| {
+                // INFO: only trips leaving past 24:00:00 can leave on `date`
+                // on the service day before.
+                let service_days = if departure.day_offset() > 0 {
+                    vec![day_before, date]
+                } else {
+                    vec![date]
+                };
+                service_days
+                    .into_iter()
+                    .map(move |service_day| (service_day, departure.on(service_day), trip))
+            })
+            .filter(|(_, leaves_at, _)| leaves_at.date() == date)
+            .filter(|(service_day, _, trip)| self.runs_on(&trip.service_id, *service_day))
+            .map(|(_, leaves_at, trip)| (leaves_at, trip))
             .collect();
-        trips.sort_by(|a, b| {
-            if let (Some(a_stop), Some(b_stop)) = (a.stop_times.first(), b.stop_times.first()) {
-                a_stop.time.cmp(&b_stop.time)
-            } else {
-                std::cmp::Ordering::Equal
-            }
-        });
-        for trip in trips.iter() {
+        trips.sort_by_key(|(leaves_at, _)| *leaves_at);
+        trips
+    }
+
+    pub fn print_running_on(&self, date: NaiveDate) {
+        for (leaves_at, trip) in self.trips_on(date) {
             // dbg!(trip);
-            if let Some(first_stop_time) = trip.stop_times.first() {
-                println!("{}: {}", trip.id, first_stop_time.time);
-            }
+            println!(
+                "{}: {} towards {}",
+                trip.id,
+                leaves_at.time(),
+                trip.headsign()
+            );
         }
     }
 
-    pub fn served_stops_today(&self) -> Vec<String> {
-        let mut set: HashSet<_> = self
-            .trips
-            .iter()
+    /// One stop time per stop served by the trips leaving on `date`, sorted
+    /// by stop name, to tell apart stops that share a name.
+    pub fn served_stops_on(&self, date: NaiveDate) -> Vec<&StopTime> {
+        let mut map: HashMap<_, _> = self
+            .trips_on(date)
+            .into_iter()
             .flat_map(|(_, trip)| {
                 trip.stop_times
                     .iter()
-                    .map(|stop_time| stop_time.name.clone())
+                    .map(|stop_time| (stop_time.stop_id.as_str(), stop_time))
             })
             .collect();
-        let mut vector: Vec<_> = set.drain().collect();
-        vector.sort();
+        let mut vector: Vec<_> = map.drain().map(|(_, stop_time)| stop_time).collect();
+        vector.sort_by(|a, b| (&a.name, &a.stop_id).cmp(&(&b.name, &b.stop_id)));
         vector
     }
 
     pub fn to_file(&self, file_name_str: &str) -> Result<(), Box<dyn std::error::Error>> {
         let serialized = ron::ser::to_string_pretty(&self, ron::ser::PrettyConfig::default())?;
         let first_size = serialized.len();
-        // INFO: IDFM prefixes all IDs, even though without the prefix the IDs
-        // do not collide. Striping them make data more concise and take up less
-        // working memory and mass storage.
-        let serialized = serialized.replace("IDFM:TRANSDEV_MARNE_LA_VALLEE:", "");
-        let serialized = serialized.replace("IDFM:", "");
+        let serialized = simplify_ids(&serialized);
         let second_size = serialized.len();
         println!("\rserialized size: {second_size} bytes (before id simplification {first_size})");
         let mut file = std::fs::File::create(file_name_str)?;
@@ -144,3 +273,11 @@ impl Timetable {
         Ok(())
     }
 }
+
+// INFO: IDFM prefixes all IDs, even though without the prefix the IDs
+// do not collide. Striping them make data more concise and take up less
+// working memory and mass storage.
+pub fn simplify_ids(serialized: &str) -> String {
+    let serialized = serialized.replace("IDFM:TRANSDEV_MARNE_LA_VALLEE:", "");
+    serialized.replace("IDFM:", "")
+}
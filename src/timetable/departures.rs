@@ -0,0 +1,193 @@
+use super::{Direction, Trip};
+use chrono::prelude::*;
+use std::collections::HashSet;
+
+/// How many days ahead to look for departures before giving up.
+const MAX_DAYS_AHEAD: usize = 7;
+
+pub struct DepartureQuery {
+    /// Ids of the stops the rider can board at.
+    pub from: HashSet<String>,
+    /// Ids of the stops the rider wants to get to, any trip going through
+    /// `from` will do when `None`.
+    pub towards: Option<HashSet<String>>,
+    /// Only keeps trips going this way along their route.
+    pub direction: Option<Direction>,
+    pub at: NaiveDateTime,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Departure<'a> {
+    pub trip: &'a Trip,
+    /// Day the trip runs on, the day before `departs_at` for trips running
+    /// past midnight.
+    pub service_day: NaiveDate,
+    pub stop_id: &'a str,
+    pub departs_at: NaiveDateTime,
+    pub wait: chrono::Duration,
+    pub headsign: &'a str,
+    /// Arrival at the destination, when one was asked for.
+    pub arrives_at: Option<NaiveDateTime>,
+}
+
+impl super::Timetable {
+    /// The next `query.count` departures from `query.from` at or after
+    /// `query.at`, in chronological order.
+    pub fn next_departures(&self, query: &DepartureQuery) -> Vec<Departure<'_>> {
+        let mut departures: Vec<Departure> = vec![];
+        // INFO: trips of the previous service day may still be running past
+        // midnight.
+        let Some(first_day) = query.at.date().pred_opt() else {
+            return departures;
+        };
+        for service_day in first_day.iter_days().take(MAX_DAYS_AHEAD + 2) {
+            departures.extend(
+                self.departures_on(service_day, query)
+                    .into_iter()
+                    .filter(|departure| departure.departs_at >= query.at),
+            );
+            departures.sort_by_key(|departure| departure.departs_at);
+            // INFO: trips of the next service day can't leave before it starts.
+            let next_day_start = service_day
+                .succ_opt()
+                .map(|next_day| next_day.and_time(NaiveTime::MIN));
+            let is_complete = departures
+                .get(query.count.saturating_sub(1))
+                .is_some_and(|last| Some(last.departs_at) < next_day_start);
+            if query.count == 0 || is_complete {
+                break;
+            }
+        }
+        departures.truncate(query.count);
+        departures
+    }
+
+    /// Every departure matching `query` of the trips running on
+    /// `service_day`, whatever their time.
+    pub fn departures_on(
+        &self,
+        service_day: NaiveDate,
+        query: &DepartureQuery,
+    ) -> Vec<Departure<'_>> {
+        self.trips
+            .iter()
+            .filter(|(_, trip)| self.runs_on(&trip.service_id, service_day))
+            .filter_map(|(_, trip)| departure_of(trip, service_day, query))
+            .collect()
+    }
+}
+
+/// Where and when `trip` lets the rider board at one of the `from` stops and,
+/// if asked for, get off later at one of the `towards` stops.
+fn departure_of<'a>(
+    trip: &'a Trip,
+    service_day: NaiveDate,
+    query: &DepartureQuery,
+) -> Option<Departure<'a>> {
+    if query.direction.is_some() && trip.direction != query.direction {
+        return None;
+    }
+    // INFO: nobody boards at the terminus.
+    let boarding = trip.stop_times[..trip.stop_times.len().saturating_sub(1)]
+        .iter()
+        .position(|stop_time| query.from.contains(&stop_time.stop_id))?;
+    let arrives_at = match &query.towards {
+        Some(towards) => {
+            let alighting = trip.stop_times[boarding + 1..]
+                .iter()
+                .find(|stop_time| towards.contains(&stop_time.stop_id))?;
+            Some(alighting.arrival.on(service_day))
+        }
+        None => None,
+    };
+    let stop_time = &trip.stop_times[boarding];
+    let departs_at = stop_time.departure.on(service_day);
+    Some(Departure {
+        trip,
+        service_day,
+        stop_id: &stop_time.stop_id,
+        departs_at,
+        wait: departs_at - query.at,
+        headsign: trip.headsign(),
+        arrives_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::Timetable;
+
+    fn query(at: NaiveDateTime, count: usize) -> DepartureQuery {
+        DepartureQuery {
+            from: HashSet::from(["A".to_owned()]),
+            towards: None,
+            direction: None,
+            at,
+            count,
+        }
+    }
+
+    #[test]
+    fn looks_past_the_weekend_and_midnight() {
+        let mut tt = Timetable::new();
+        tt.add_service("W", WEEKDAYS, date(2024, 1, 1), date(2024, 1, 31));
+        tt.add_trip("day", "W", "R", &[("A", "07:00"), ("B", "07:20")]);
+        tt.add_trip("late", "W", "R", &[("A", "23:30"), ("B", "23:50")]);
+        tt.add_trip("night", "W", "R", &[("A", "24:40"), ("B", "25:00")]);
+        let friday = date(2024, 1, 5);
+        let monday = date(2024, 1, 8);
+        let listed = |departures: Vec<Departure>| -> Vec<_> {
+            departures
+                .iter()
+                .map(|departure| {
+                    (
+                        departure.trip.id.clone(),
+                        departure.service_day,
+                        departure.departs_at,
+                    )
+                })
+                .collect()
+        };
+        let at = |date: NaiveDate, hour| date.and_hms_opt(hour, 0, 0).unwrap();
+        let saturday_00_40 = date(2024, 1, 6).and_hms_opt(0, 40, 0).unwrap();
+        let monday_23_30 = monday.and_hms_opt(23, 30, 0).unwrap();
+        assert_eq!(
+            listed(tt.next_departures(&query(at(friday, 22), 4))),
+            [
+                (
+                    "late".to_owned(),
+                    friday,
+                    friday.and_hms_opt(23, 30, 0).unwrap()
+                ),
+                ("night".to_owned(), friday, saturday_00_40),
+                ("day".to_owned(), monday, at(monday, 7)),
+                ("late".to_owned(), monday, monday_23_30),
+            ]
+        );
+        // INFO: the trip of the Friday service still leaves on Saturday.
+        assert_eq!(
+            listed(tt.next_departures(&query(at(date(2024, 1, 6), 0), 1))),
+            [("night".to_owned(), friday, saturday_00_40)]
+        );
+        // INFO: nothing runs after the end of the calendar.
+        let last_day = date(2024, 1, 31);
+        assert_eq!(
+            listed(tt.next_departures(&query(at(last_day, 23), 3))),
+            [
+                (
+                    "late".to_owned(),
+                    last_day,
+                    last_day.and_hms_opt(23, 30, 0).unwrap()
+                ),
+                (
+                    "night".to_owned(),
+                    last_day,
+                    date(2024, 2, 1).and_hms_opt(0, 40, 0).unwrap()
+                ),
+            ]
+        );
+    }
+}
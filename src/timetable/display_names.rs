@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet};
+
+/// Marks names shortened by `max_length`.
+const ELLIPSIS: char = '…';
+
+/// How to make stop names fit on a stop display or a terminal line.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default)]
+pub struct DisplayRules {
+    /// Words and their abbreviation, as `("Gare", "G.")`. Only whole words
+    /// are abbreviated.
+    pub abbreviations: Vec<(String, String)>,
+    /// Most characters of a display name, names still too long once
+    /// abbreviated are truncated.
+    pub max_length: Option<usize>,
+}
+
+impl Default for DisplayRules {
+    fn default() -> Self {
+        let abbreviations = [
+            ("Gare", "G."),
+            ("Saint", "St"),
+            ("Sainte", "Ste"),
+            ("Avenue", "Av."),
+            ("Boulevard", "Bd"),
+            ("Place", "Pl."),
+        ];
+        Self {
+            abbreviations: abbreviations
+                .into_iter()
+                .map(|(word, abbreviation)| (word.to_owned(), abbreviation.to_owned()))
+                .collect(),
+            max_length: None,
+        }
+    }
+}
+
+impl DisplayRules {
+    /// Reads rules from a RON file, as in
+    /// `(abbreviations: [("Gare", "G.")], max_length: Some(20))`.
+    pub fn from_file(file_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let buf = std::fs::read_to_string(file_name)?;
+        Ok(ron::from_str(&buf)?)
+    }
+
+    fn abbreviate(&self, name: &str) -> String {
+        self.abbreviations
+            .iter()
+            .fold(name.to_owned(), |name, (word, abbreviation)| {
+                replace_word(&name, word, abbreviation)
+            })
+    }
+}
+
+impl super::Timetable {
+    /// Computes the display name of every stop. Names that come out alike
+    /// once shortened keep more of their end, or get numbered, to still be
+    /// told apart.
+    pub fn build_display_names(&mut self, rules: &DisplayRules) {
+        let mut names: Vec<&str> = self
+            .stops
+            .values()
+            .filter_map(|stop| stop.name.as_deref())
+            .collect();
+        names.sort();
+        names.dedup();
+        let mut by_display: HashMap<String, Vec<&str>> = HashMap::new();
+        for name in names {
+            let abbreviated = rules.abbreviate(name);
+            let display = match rules.max_length {
+                Some(max_length) => truncate(&abbreviated, max_length, 0),
+                None => abbreviated,
+            };
+            by_display.entry(display).or_default().push(name);
+        }
+
+        let mut display_names: HashMap<&str, String> = HashMap::new();
+        let mut collided = vec![];
+        for (display, names) in by_display {
+            if names.len() == 1 {
+                display_names.insert(names[0], display);
+            } else {
+                collided.push(names);
+            }
+        }
+        // INFO: names told apart within a group can still come out as the
+        // display name of another one, such as "G. de Lagny 1".
+        let mut taken: HashSet<String> = display_names.values().cloned().collect();
+        collided.sort();
+        for names in collided {
+            for (name, display) in names.iter().zip(distinguish(rules, &names)) {
+                let display = if taken.contains(&display) {
+                    let abbreviated = rules.abbreviate(name);
+                    (1..)
+                        .map(|number| numbered(rules, &abbreviated, number))
+                        .find(|numbered| !taken.contains(numbered))
+                        .unwrap_or(display)
+                } else {
+                    display
+                };
+                taken.insert(display.clone());
+                display_names.insert(name, display);
+            }
+        }
+        self.display_names = self
+            .stops
+            .values()
+            .filter_map(|stop| {
+                let display = display_names.get(stop.name.as_deref()?)?;
+                Some((stop.id.clone(), display.clone()))
+            })
+            .collect();
+    }
+
+    /// Display name of a stop, its name when it has none.
+    pub fn display_name(&self, stop_id: &str) -> Option<&str> {
+        self.display_names
+            .get(stop_id)
+            .map(String::as_str)
+            .or_else(|| self.stops.get(stop_id)?.name.as_deref())
+    }
+}
+
+/// Display names for names that came out the same. When truncating made
+/// them collide, more of their ends is kept until they differ, and they are
+/// numbered as a last resort.
+fn distinguish(rules: &DisplayRules, names: &[&str]) -> Vec<String> {
+    let abbreviated: Vec<_> = names.iter().map(|name| rules.abbreviate(name)).collect();
+    if let Some(max_length) = rules.max_length {
+        for kept_end in 1..max_length.saturating_sub(1) {
+            let displays: Vec<_> = abbreviated
+                .iter()
+                .map(|name| truncate(name, max_length, kept_end))
+                .collect();
+            let mut unique = displays.clone();
+            unique.sort();
+            unique.dedup();
+            if unique.len() == displays.len() {
+                return displays;
+            }
+        }
+    }
+    // INFO: abbreviations alone can make names collide, such as "Gare" and
+    // "G.".
+    abbreviated
+        .iter()
+        .enumerate()
+        .map(|(index, name)| numbered(rules, name, index + 1))
+        .collect()
+}
+
+/// `name` followed by `number`, shortened to still fit `max_length`. Only
+/// the number is left when there is no room for the name.
+fn numbered(rules: &DisplayRules, name: &str, number: usize) -> String {
+    let number = number.to_string();
+    match rules.max_length {
+        Some(max_length) if max_length <= number.len() + 1 => number,
+        Some(max_length) => format!(
+            "{} {number}",
+            truncate(name, max_length - number.len() - 1, 0)
+        ),
+        None => format!("{name} {number}"),
+    }
+}
+
+/// Shortens `name` to `max_length` characters with an ellipsis, keeping its
+/// last `kept_end` characters.
+fn truncate(name: &str, max_length: usize, kept_end: usize) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    if chars.len() <= max_length {
+        return name.to_owned();
+    }
+    let start_length = max_length.saturating_sub(kept_end + 1);
+    let start: String = chars[..start_length].iter().collect();
+    let end: String = chars[chars.len() - kept_end.min(chars.len())..]
+        .iter()
+        .collect();
+    format!("{}{ELLIPSIS}{}", start.trim_end(), end.trim_start())
+}
+
+/// Replaces the whole word occurrences of `word` in `name`.
+fn replace_word(name: &str, word: &str, replacement: &str) -> String {
+    let mut replaced = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(index) = rest.find(word) {
+        let (before, after) = (&rest[..index], &rest[index + word.len()..]);
+        replaced.push_str(before);
+        let starts_word = !replaced
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        let ends_word = !after.chars().next().is_some_and(char::is_alphanumeric);
+        replaced.push_str(if starts_word && ends_word {
+            replacement
+        } else {
+            word
+        });
+        rest = after;
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::Timetable;
+
+    fn display_names(names: &[&str], max_length: Option<usize>) -> Vec<(String, String)> {
+        let mut tt = Timetable::new();
+        for (index, name) in names.iter().enumerate() {
+            tt.add_stop(&index.to_string(), name, None);
+        }
+        tt.build_display_names(&DisplayRules {
+            max_length,
+            ..Default::default()
+        });
+        let mut display_names: Vec<_> = tt
+            .stops
+            .keys()
+            .map(|stop_id| {
+                let name = tt.stops[stop_id].name.clone().unwrap();
+                (name, tt.display_name(stop_id).unwrap().to_owned())
+            })
+            .collect();
+        display_names.sort();
+        display_names
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, display)| (name.to_string(), display.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn truncates_keeping_the_end() {
+        assert_eq!(truncate("Gare de Chelles", 15, 0), "Gare de Chelles");
+        assert_eq!(truncate("Gare de Chelles", 10, 0), "Gare de C…");
+        assert_eq!(truncate("Gare de Chelles", 10, 3), "Gare d…les");
+        // INFO: no space is left next to the ellipsis.
+        assert_eq!(truncate("Gare de Chelles", 9, 0), "Gare de…");
+    }
+
+    #[test]
+    fn keeps_more_of_the_end_to_tell_names_apart() {
+        assert_eq!(
+            display_names(
+                &["Noisy-le-Grand Mairie", "Noisy-le-Grand Mont d'Est"],
+                Some(12)
+            ),
+            pairs(&[
+                ("Noisy-le-Grand Mairie", "Noisy-le-G…e"),
+                ("Noisy-le-Grand Mont d'Est", "Noisy-le-G…t"),
+            ])
+        );
+    }
+
+    #[test]
+    fn numbers_stay_unique_across_names() {
+        assert_eq!(
+            display_names(&["G. de Lagny", "G. de Lagny 1", "Gare de Lagny"], None),
+            pairs(&[
+                ("G. de Lagny", "G. de Lagny 2"),
+                ("G. de Lagny 1", "G. de Lagny 1"),
+                ("Gare de Lagny", "G. de Lagny 3"),
+            ])
+        );
+        for max_length in [2, 4] {
+            let display_names = display_names(&["G. A", "Gare A"], Some(max_length));
+            assert!(display_names
+                .iter()
+                .all(|(_, display)| display.chars().count() <= max_length));
+            assert_ne!(display_names[0].1, display_names[1].1);
+        }
+    }
+}
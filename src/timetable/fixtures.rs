@@ -0,0 +1,117 @@
+//! Small timetables for the tests of the timetable modules.
+
+use super::my_gtfs_structs::{Calendar, CalendarDate, Exception, Stop};
+use super::{ServiceTime, StopTime, Timetable, Trip};
+use chrono::NaiveDate;
+
+pub fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+pub fn time(hh_mm: &str) -> ServiceTime {
+    format!("{hh_mm}:00").parse().unwrap()
+}
+
+pub fn stop(id: &str, name: &str) -> Stop {
+    Stop {
+        id: id.to_owned(),
+        code: None,
+        name: Some(name.to_owned()),
+        description: None,
+        parent_station: None,
+        zone_id: None,
+        url: None,
+        longitude: None,
+        latitude: None,
+        timezone: None,
+        level_id: None,
+        platform_code: None,
+        tts_name: None,
+    }
+}
+
+impl Timetable {
+    pub fn add_stop(&mut self, id: &str, name: &str, parent_station: Option<&str>) -> &mut Stop {
+        let mut stop = stop(id, name);
+        stop.parent_station = parent_station.map(str::to_owned);
+        self.stops.insert(id.to_owned(), stop);
+        self.stops.get_mut(id).unwrap()
+    }
+
+    /// A service running on the `weekdays` flagged, Monday first, between
+    /// two dates.
+    pub fn add_service(&mut self, id: &str, weekdays: [bool; 7], start: NaiveDate, end: NaiveDate) {
+        let [monday, tuesday, wednesday, thursday, friday, saturday, sunday] = weekdays;
+        self.calendar.insert(
+            id.to_owned(),
+            Calendar {
+                id: id.to_owned(),
+                monday,
+                tuesday,
+                wednesday,
+                thursday,
+                friday,
+                saturday,
+                sunday,
+                start_date: start,
+                end_date: end,
+            },
+        );
+    }
+
+    pub fn add_exception(&mut self, service_id: &str, date: NaiveDate, exception_type: Exception) {
+        self.calendar_dates
+            .entry(service_id.to_owned())
+            .or_default()
+            .push(CalendarDate {
+                service_id: service_id.to_owned(),
+                date,
+                exception_type,
+            });
+    }
+
+    /// A trip calling at `stops`, given as stop id and "HH:MM", arriving and
+    /// leaving at the same time. Stop names come from `self.stops`.
+    pub fn add_trip(
+        &mut self,
+        id: &str,
+        service_id: &str,
+        route_id: &str,
+        stops: &[(&str, &str)],
+    ) -> &mut Trip {
+        let stop_times = stops
+            .iter()
+            .map(|(stop_id, hh_mm)| {
+                let stop = self.stops.get(*stop_id);
+                StopTime {
+                    arrival: time(hh_mm),
+                    departure: time(hh_mm),
+                    stop_id: stop_id.to_string(),
+                    name: stop
+                        .and_then(|stop| stop.name.clone())
+                        .unwrap_or_else(|| stop_id.to_string()),
+                    parent_station: stop.and_then(|stop| stop.parent_station.clone()),
+                    platform_code: None,
+                }
+            })
+            .collect();
+        self.trips.insert(
+            id.to_owned(),
+            Trip {
+                id: id.to_owned(),
+                service_id: service_id.to_owned(),
+                route_id: route_id.to_owned(),
+                stop_times,
+                headsign: None,
+                short_name: None,
+                direction: None,
+            },
+        );
+        self.trips
+            .get_vec_mut(id)
+            .and_then(|trips| trips.last_mut())
+            .unwrap()
+    }
+}
+
+pub const WEEKDAYS: [bool; 7] = [true, true, true, true, true, false, false];
@@ -1,125 +1,208 @@
-use super::Timetable;
-use spinoff::{spinners, Spinner};
-
-// #[allow(dead_code)]
-// pub fn gtfs_extract(arg: String) -> std::ops::ControlFlow<()> {
-//     let mut spinner = Spinner::new(spinners::Dots, format!("Parsing GTFS of: {arg}"), None);
-//     let gtfs = match gtfs_structures::Gtfs::new(&arg) {
-//         Ok(val) => val,
-//         Err(error) => {
-//             spinner.fail(&error.to_string());
-//             return std::ops::ControlFlow::Continue(());
-//         }
-//     };
-//     spinner.success("Parsing complete");
-//     println!("there are {} stops in the gtfs", gtfs.stops.len());
-//     let mut potator = Timetable::new();
-//     for (id, route) in gtfs.routes.iter().filter(|route| route.0 == "IDFM:C02298") {
-//         potator.routes.insert(id.clone(), route.clone());
-//         println!("route id {id}");
-//         dbg!(route);
-//         for (id, trip) in gtfs
-//             .trips
-//             .iter()
-//             .filter(|(_, trip)| trip.route_id == route.id)
-//         {
-//             potator.trips.insert(id.clone(), trip.into());
-//             if let Some(service_cal) = gtfs.calendar.get(&trip.service_id) {
-//                 potator
-//                     .calendar
-//                     .insert(trip.service_id.clone(), service_cal.clone());
-//                 // if !potator.runs_on_weekday(service_cal) {
-//                 //     continue;
-//                 // }
-//             }
-//             if let Some(service_date) = gtfs.calendar_dates.get(&trip.service_id) {
-//                 potator
-//                     .calendar_dates
-//                     .insert(trip.service_id.clone(), service_date.clone());
-//             }
-//             for stop_time in &trip.stop_times {
-//                 potator
-//                     .stops
-//                     .insert(stop_time.stop.id.clone(), (*stop_time.stop).clone());
-//                 // INFO: time of day is stored in seconds
-//                 // let time_of_day = chrono::NaiveTime::from_num_seconds_from_midnight_opt(
-//                 //     stop_time.departure_time.unwrap(),
-//                 //     0,
-//                 // )
-//                 // .unwrap();
-//                 // if let Some(name) = &stop_time.stop.name {
-//                 //     println!("{id}: {name}: {time_of_day:#?}");
-//                 // }
-//                 // break;
-//             }
-//             // return std::ops::ControlFlow::Continue(());
-//         }
-//     }
-//     let mut spinner = Spinner::new(spinners::Dots, format!("Serializing"), None);
-//     let serialized = ron::to_string(&potator).unwrap();
-//     let mut file = std::fs::File::create("potator.json").unwrap();
-//     std::io::Write::write(&mut file, serialized.as_bytes()).unwrap();
-//     spinner.success("Done serialising");
-//     std::ops::ControlFlow::Continue(())
-// }
+use super::stations::group_stations;
+use super::{StopTime, Timetable, Trip};
+use crate::extractor::{trip_segment, ExtractReport};
+use std::collections::{BTreeSet, HashSet};
 
 impl Timetable {
-    pub fn gtfs_extract(&mut self, from_path_str: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut spinner = Spinner::new(
-            spinners::Dots,
-            format!("Parsing GTFS of: {from_path_str}"),
-            None,
-        );
-        let gtfs = match gtfs_structures::Gtfs::new(from_path_str) {
-            Ok(val) => val,
-            Err(error) => {
-                spinner.fail(&error.to_string());
-                return Err(error.into());
+    pub fn gtfs_extract(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        route_ids: &[String],
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>> {
+        let mut report = ExtractReport::default();
+        for route_id in route_ids {
+            if !gtfs.routes.contains_key(route_id) {
+                return Err(format!("no route with id {route_id} in the gtfs").into());
             }
-        };
-        spinner.success("Parsing complete");
-        println!("there are {} stops in the gtfs", gtfs.stops.len());
-        for (id, route) in gtfs.routes.iter().filter(|route| route.0 == "IDFM:C02298") {
+        }
+        for (id, route) in gtfs.routes.iter().filter(|(id, _)| route_ids.contains(id)) {
             self.routes.insert(id.clone(), route.clone().into());
-            println!("route id {id}");
-            dbg!(route);
             for (id, trip) in gtfs
                 .trips
                 .iter()
                 .filter(|(_, trip)| trip.route_id == route.id)
             {
                 self.trips.insert(id.clone(), trip.into());
-                if let Some(service_cal) = gtfs.calendar.get(&trip.service_id) {
-                    self.calendar
-                        .insert(trip.service_id.clone(), service_cal.clone().into());
-                }
-                if let Some(service_date) = gtfs.calendar_dates.get(&trip.service_id) {
-                    self.calendar_dates.insert(
-                        trip.service_id.clone(),
-                        service_date
-                            .iter()
-                            .map(|item| -> super::my_gtfs_structs::CalendarDate {
-                                item.clone().into()
-                            })
-                            .collect(),
-                    );
-                }
+                report.journeys += 1;
+                self.copy_service(gtfs, &trip.service_id, &mut report);
                 for stop_time in &trip.stop_times {
                     self.stops
                         .insert(stop_time.stop.id.clone(), (*stop_time.stop).clone().into());
-                    // INFO: time of day is stored in seconds
-                    // let time_of_day = chrono::NaiveTime::from_num_seconds_from_midnight_opt(
-                    //     stop_time.departure_time.unwrap(),
-                    //     0,
-                    // )
-                    // .unwrap();
-                    // if let Some(name) = &stop_time.stop.name {
-                    //     println!("{id}: {name}: {time_of_day:#?}");
-                    // }
-                    // break;
                 }
             }
         }
-        Ok(())
+        self.build_indexes(gtfs);
+        Ok(report)
+    }
+
+    /// Extracts every trip that serves one of the `origins` and then one of
+    /// the `destinations`, whatever its route, trimmed to that segment, see
+    /// `extractor::trip_segment`.
+    pub fn gtfs_extract_stop_pairs(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        origins: &HashSet<String>,
+        destinations: &HashSet<String>,
+    ) -> Result<ExtractReport, Box<dyn std::error::Error>> {
+        let mut report = ExtractReport::default();
+        for (id, trip) in &gtfs.trips {
+            let Some(segment) = trip_segment(trip, origins, destinations) else {
+                continue;
+            };
+            report.journeys += 1;
+            let mut extracted = Trip::from(trip);
+            extracted.stop_times = trip.stop_times[segment.clone()]
+                .iter()
+                .filter_map(|stop_time| StopTime::try_from(stop_time).ok())
+                .collect();
+            self.trips.insert(id.clone(), extracted);
+            if let Some(route) = gtfs.routes.get(&trip.route_id) {
+                self.routes.insert(route.id.clone(), route.clone().into());
+            }
+            self.copy_service(gtfs, &trip.service_id, &mut report);
+            for stop_time in &trip.stop_times[segment] {
+                self.stops
+                    .insert(stop_time.stop.id.clone(), (*stop_time.stop).clone().into());
+            }
+        }
+        if report.journeys == 0 {
+            return Err("no trip was available".into());
+        }
+        self.build_indexes(gtfs);
+        Ok(report)
+    }
+
+    /// Builds what is derived from the extracted trips and stops.
+    fn build_indexes(&mut self, gtfs: &gtfs_structures::Gtfs) {
+        self.copy_stations(gtfs);
+        self.build_stations();
+        self.import_transfers(gtfs);
+        self.build_route_patterns();
+        self.build_service_calendar();
+    }
+
+    /// A timetable with only the stops of `gtfs` and their stations, enough
+    /// to resolve stops against before extracting.
+    pub fn stop_index(gtfs: &gtfs_structures::Gtfs) -> Self {
+        let mut index = Self::new();
+        index.stops = gtfs
+            .stops
+            .iter()
+            .map(|(id, stop)| (id.clone(), (**stop).clone().into()))
+            .collect();
+        let served: BTreeSet<&str> = gtfs
+            .trips
+            .values()
+            .flat_map(|trip| &trip.stop_times)
+            .map(|stop_time| stop_time.stop.id.as_str())
+            .collect();
+        index.stations = group_stations(&index.stops, &served);
+        index
+    }
+
+    /// Copies the stations the extracted stops belong to. Stops only refer to
+    /// them by id, this keeps their names around.
+    fn copy_stations(&mut self, gtfs: &gtfs_structures::Gtfs) {
+        let station_ids: Vec<_> = self
+            .stops
+            .values()
+            .filter_map(|stop| stop.parent_station.clone())
+            .filter(|station_id| !self.stops.contains_key(station_id))
+            .collect();
+        for station_id in station_ids {
+            if let Some(station) = gtfs.stops.get(&station_id) {
+                self.stops.insert(station_id, (**station).clone().into());
+            }
+        }
+    }
+
+    /// Copies the calendar and calendar dates of a service. Many trips share
+    /// a service, this only does the work the first time.
+    fn copy_service(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        service_id: &str,
+        report: &mut ExtractReport,
+    ) {
+        if self.calendar.contains_key(service_id) || self.calendar_dates.contains_key(service_id) {
+            return;
+        }
+        report.services += 1;
+        let has_pattern = gtfs.calendar.contains_key(service_id);
+        if let Some(service_cal) = gtfs.calendar.get(service_id) {
+            self.calendar
+                .insert(service_id.to_owned(), service_cal.clone().into());
+            report.patterns += 1;
+        }
+        if let Some(service_date) = gtfs.calendar_dates.get(service_id) {
+            report.exceptions += service_date.len();
+            report.dates_only_services += usize::from(!has_pattern);
+            self.calendar_dates.insert(
+                service_id.to_owned(),
+                service_date
+                    .iter()
+                    .map(|item| -> super::my_gtfs_structs::CalendarDate { item.clone().into() })
+                    .collect(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gtfs_structures::{Gtfs, Stop};
+    use std::sync::Arc;
+
+    fn stop_time(id: &str, seconds: u32) -> gtfs_structures::StopTime {
+        gtfs_structures::StopTime {
+            stop: Arc::new(Stop {
+                id: id.to_owned(),
+                name: Some(id.to_owned()),
+                ..Default::default()
+            }),
+            departure_time: Some(seconds),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stop_pairs_keep_stops_past_midnight() {
+        let mut gtfs = Gtfs::default();
+        gtfs.trips.insert(
+            "T".to_owned(),
+            gtfs_structures::Trip {
+                id: "T".to_owned(),
+                service_id: "S".to_owned(),
+                route_id: "R".to_owned(),
+                stop_times: vec![
+                    stop_time("A", 23 * 3600),
+                    stop_time("B", 23 * 3600 + 1800),
+                    stop_time("C", 24 * 3600 + 600),
+                    stop_time("D", 24 * 3600 + 1200),
+                ],
+                ..Default::default()
+            },
+        );
+        let stops = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+        let mut tt = Timetable::new();
+        let report = tt
+            .gtfs_extract_stop_pairs(&gtfs, &stops(&["B"]), &stops(&["C"]))
+            .unwrap();
+        assert_eq!(report.journeys, 1);
+        let trip = &tt.trips.get_vec("T").unwrap()[0];
+        let stop_ids: Vec<_> = trip
+            .stop_times
+            .iter()
+            .map(|stop_time| stop_time.stop_id.as_str())
+            .collect();
+        assert_eq!(stop_ids, ["B", "C"]);
+        assert_eq!(trip.stop_times[1].departure.to_string(), "24:10:00");
+        assert!(tt.stops.contains_key("C") && !tt.stops.contains_key("D"));
+
+        let mut tt = Timetable::new();
+        assert!(tt
+            .gtfs_extract_stop_pairs(&gtfs, &stops(&["C"]), &stops(&["A"]))
+            .is_err());
     }
 }
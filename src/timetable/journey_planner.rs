@@ -0,0 +1,370 @@
+use super::{StopTime, Trip};
+use chrono::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+pub struct JourneyQuery {
+    /// Ids of the stops the rider can leave from.
+    pub from: HashSet<String>,
+    /// Ids of the stops the rider wants to get to.
+    pub to: HashSet<String>,
+    pub at: NaiveDateTime,
+    /// Most changes between trips an itinerary may have.
+    pub max_transfers: usize,
+}
+
+/// Part of an itinerary spent on a single trip.
+#[derive(Debug, Clone)]
+pub struct Leg<'a> {
+    pub trip: &'a Trip,
+    pub service_day: NaiveDate,
+    /// Index in `trip.stop_times` of the stop the rider boards at.
+    pub board: usize,
+    /// Index in `trip.stop_times` of the stop the rider gets off at.
+    pub alight: usize,
+}
+
+impl Leg<'_> {
+    pub fn from(&self) -> &StopTime {
+        &self.trip.stop_times[self.board]
+    }
+
+    pub fn to(&self) -> &StopTime {
+        &self.trip.stop_times[self.alight]
+    }
+
+    pub fn departs_at(&self) -> NaiveDateTime {
+        self.from().departure.on(self.service_day)
+    }
+
+    pub fn arrives_at(&self) -> NaiveDateTime {
+        self.to().arrival.on(self.service_day)
+    }
+}
+
+#[derive(Debug)]
+pub struct Itinerary<'a> {
+    pub legs: Vec<Leg<'a>>,
+}
+
+impl Itinerary<'_> {
+    pub fn departs_at(&self) -> Option<NaiveDateTime> {
+        Some(self.legs.first()?.departs_at())
+    }
+
+    pub fn arrives_at(&self) -> Option<NaiveDateTime> {
+        Some(self.legs.last()?.arrives_at())
+    }
+
+    pub fn transfers(&self) -> usize {
+        self.legs.len().saturating_sub(1)
+    }
+}
+
+/// A trip going from one stop to the next, the unit the planner scans.
+struct Connection {
+    /// Index of the trip in the run list.
+    run: usize,
+    /// Index in `stop_times` of the stop the connection leaves from.
+    index: usize,
+    departs_at: NaiveDateTime,
+    arrives_at: NaiveDateTime,
+}
+
+/// How a stop was reached in a round of the planner.
+#[derive(Clone)]
+struct Label {
+    arrives_at: NaiveDateTime,
+    run: usize,
+    board: usize,
+    alight: usize,
+    /// Stop the rider got off at on the previous leg, labelled in the round
+    /// before, `None` on the first leg.
+    previous: Option<String>,
+}
+
+/// Earliest time the rider can board at a stop, and where they got off to
+/// get there.
+#[derive(Clone)]
+struct Boarding {
+    at: NaiveDateTime,
+    previous: Option<String>,
+}
+
+impl super::Timetable {
+    /// Earliest arrival itineraries from `query.from` to `query.to` leaving
+    /// at or after `query.at`, one for each number of transfers that gets
+    /// there earlier than with fewer, in increasing number of transfers.
+    ///
+    /// This is a connection scan, run once per allowed number of trips.
+    /// Only trips of the day before, the day of and the day after
+    /// `query.at` are looked at.
+    pub fn plan_journeys(&self, query: &JourneyQuery) -> Vec<Itinerary<'_>> {
+        let start_day = query.at.date();
+        let service_days: Vec<_> = [start_day.pred_opt(), Some(start_day), start_day.succ_opt()]
+            .into_iter()
+            .flatten()
+            .collect();
+        let runs: Vec<(&Trip, NaiveDate)> = service_days
+            .iter()
+            .flat_map(|service_day| {
+                self.trips
+                    .iter()
+                    .filter(|(_, trip)| self.runs_on(&trip.service_id, *service_day))
+                    .map(|(_, trip)| (trip, *service_day))
+            })
+            .collect();
+        let mut connections: Vec<_> = runs
+            .iter()
+            .enumerate()
+            .flat_map(|(run, (trip, service_day))| {
+                trip.stop_times
+                    .windows(2)
+                    .enumerate()
+                    .map(move |(index, pair)| Connection {
+                        run,
+                        index,
+                        departs_at: pair[0].departure.on(*service_day),
+                        arrives_at: pair[1].arrival.on(*service_day),
+                    })
+            })
+            .filter(|connection| connection.departs_at >= query.at)
+            .collect();
+        connections.sort_by_key(|connection| connection.departs_at);
+
+        let station_stops = self.station_stops();
+        // INFO: the rider may walk to another stop of the station before
+        // boarding the first trip.
+        let mut boardings: HashMap<String, Boarding> = HashMap::new();
+        for origin in &query.from {
+            let walks = self
+                .transfers_from(origin, &station_stops)
+                .into_iter()
+                .filter(|(stop_id, _)| stop_id != origin)
+                .map(|(stop_id, walk)| (stop_id, query.at + walk));
+            for (stop_id, at) in [(origin.as_str(), query.at)].into_iter().chain(walks) {
+                improve(&mut boardings, stop_id, Boarding { at, previous: None });
+            }
+        }
+        // INFO: as in RAPTOR, each round only keeps the stops it reached
+        // earlier than any round before. A label's previous stop is then
+        // always labelled in the round just before, which is how `legs_to`
+        // walks back.
+        let mut rounds: Vec<HashMap<String, Label>> = vec![];
+        let mut best: HashMap<String, NaiveDateTime> = HashMap::new();
+        let mut itineraries = vec![];
+        let mut best_arrival: Option<NaiveDateTime> = None;
+        for _ in 0..=query.max_transfers {
+            if let Some(previous_labels) = rounds.last() {
+                boardings = HashMap::new();
+                for (stop_id, label) in previous_labels {
+                    for (next_stop_id, transfer_time) in
+                        self.transfers_from(stop_id, &station_stops)
+                    {
+                        let boarding = Boarding {
+                            at: label.arrives_at + transfer_time,
+                            previous: Some(stop_id.clone()),
+                        };
+                        improve(&mut boardings, next_stop_id, boarding);
+                    }
+                }
+            }
+            let labels = scan(&runs, &connections, &boardings, &mut best);
+            if labels.is_empty() {
+                break;
+            }
+
+            let arrival = query
+                .to
+                .iter()
+                .filter_map(|stop_id| Some((stop_id, labels.get(stop_id)?.arrives_at)))
+                .min_by_key(|(_, arrives_at)| *arrives_at);
+            rounds.push(labels);
+            let Some((stop_id, arrives_at)) = arrival else {
+                continue;
+            };
+            if best_arrival.is_some_and(|best| best <= arrives_at) {
+                continue;
+            }
+            best_arrival = Some(arrives_at);
+            itineraries.push(Itinerary {
+                legs: legs_to(&runs, &rounds, stop_id),
+            });
+        }
+        itineraries
+    }
+}
+
+fn improve(boardings: &mut HashMap<String, Boarding>, stop_id: &str, boarding: Boarding) {
+    match boardings.get(stop_id) {
+        Some(known) if known.at <= boarding.at => {}
+        _ => {
+            boardings.insert(stop_id.to_owned(), boarding);
+        }
+    }
+}
+
+/// One pass of the connection scan: labels every stop reachable by boarding
+/// a single trip at one of `boardings` earlier than the `best` arrival known
+/// there, which it updates.
+fn scan(
+    runs: &[(&Trip, NaiveDate)],
+    connections: &[Connection],
+    boardings: &HashMap<String, Boarding>,
+    best: &mut HashMap<String, NaiveDateTime>,
+) -> HashMap<String, Label> {
+    let mut labels = HashMap::new();
+    // INFO: for each run the rider is on, where they boarded it and from
+    // which previous leg.
+    let mut boarded: HashMap<usize, (usize, Option<String>)> = HashMap::new();
+    for connection in connections {
+        let (trip, _) = runs[connection.run];
+        let from = &trip.stop_times[connection.index];
+        let (board, previous) = match boarded.entry(connection.run) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match boardings.get(&from.stop_id) {
+                Some(boarding) if boarding.at <= connection.departs_at => {
+                    entry.insert((connection.index, boarding.previous.clone()))
+                }
+                _ => continue,
+            },
+        };
+        let to = &trip.stop_times[connection.index + 1];
+        if best
+            .get(&to.stop_id)
+            .is_some_and(|arrives_at| *arrives_at <= connection.arrives_at)
+        {
+            continue;
+        }
+        best.insert(to.stop_id.clone(), connection.arrives_at);
+        labels.insert(
+            to.stop_id.clone(),
+            Label {
+                arrives_at: connection.arrives_at,
+                run: connection.run,
+                board: *board,
+                alight: connection.index + 1,
+                previous: previous.clone(),
+            },
+        );
+    }
+    labels
+}
+
+/// Walks the labels back from `stop_id`, labelled in the last round, to the
+/// origin.
+fn legs_to<'a>(
+    runs: &[(&'a Trip, NaiveDate)],
+    rounds: &[HashMap<String, Label>],
+    stop_id: &str,
+) -> Vec<Leg<'a>> {
+    let mut legs = vec![];
+    let mut next = Some(stop_id.to_owned());
+    for labels in rounds.iter().rev() {
+        let Some(label) = next.and_then(|stop_id| labels.get(&stop_id)) else {
+            break;
+        };
+        let (trip, service_day) = runs[label.run];
+        legs.push(Leg {
+            trip,
+            service_day,
+            board: label.board,
+            alight: label.alight,
+        });
+        next = label.previous.clone();
+    }
+    legs.reverse();
+    legs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::transfers::{Transfer, TransferType};
+    use crate::timetable::Timetable;
+
+    /// A to B1 at 08:00, then from B2, a quay of the same station, to C at
+    /// 08:25 and 08:35. Changing from B1 to B2 takes ten minutes.
+    fn timetable() -> Timetable {
+        let mut tt = Timetable::new();
+        tt.add_service("wk", WEEKDAYS, date(2026, 10, 1), date(2026, 12, 31));
+        tt.add_stop("A", "Alpha", None);
+        tt.add_stop("B", "Bravo", None);
+        tt.add_stop("B1", "Bravo", Some("B"));
+        tt.add_stop("B2", "Bravo", Some("B"));
+        tt.add_stop("C", "Charlie", None);
+        tt.add_trip("t1", "wk", "r", &[("A", "08:00"), ("B1", "08:20")]);
+        tt.add_trip("t2", "wk", "r", &[("B2", "08:25"), ("C", "08:50")]);
+        tt.add_trip("t3", "wk", "r", &[("B2", "08:35"), ("C", "09:00")]);
+        tt.transfers.insert(
+            "B1".to_owned(),
+            vec![Transfer {
+                to_stop_id: "B2".to_owned(),
+                transfer_type: TransferType::MinTime,
+                min_time: Some(600),
+            }],
+        );
+        tt
+    }
+
+    fn query(from: &str, to: &str, max_transfers: usize) -> JourneyQuery {
+        JourneyQuery {
+            from: HashSet::from([from.to_owned()]),
+            to: HashSet::from([to.to_owned()]),
+            at: date(2026, 10, 5).and_hms_opt(7, 30, 0).unwrap(),
+            max_transfers,
+        }
+    }
+
+    /// Trip ids of the legs, checking each leg leaves after the previous
+    /// one arrives.
+    fn trips(itinerary: &Itinerary) -> Vec<String> {
+        for pair in itinerary.legs.windows(2) {
+            assert!(pair[0].arrives_at() <= pair[1].departs_at());
+        }
+        itinerary
+            .legs
+            .iter()
+            .map(|leg| leg.trip.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn direct_trip() {
+        let tt = timetable();
+        let itineraries = tt.plan_journeys(&query("A", "B1", 2));
+        assert_eq!(itineraries.len(), 1);
+        assert_eq!(trips(&itineraries[0]), ["t1"]);
+        assert_eq!(itineraries[0].legs[0].from().stop_id, "A");
+    }
+
+    #[test]
+    fn changes_with_the_transfer_minimum_time() {
+        let tt = timetable();
+        let itineraries = tt.plan_journeys(&query("A", "C", 2));
+        assert_eq!(itineraries.len(), 1);
+        // INFO: t2 leaves B2 five minutes after t1 gets to B1, less than the
+        // ten the transfer needs.
+        assert_eq!(trips(&itineraries[0]), ["t1", "t3"]);
+        assert_eq!(itineraries[0].transfers(), 1);
+        assert_eq!(
+            itineraries[0].arrives_at(),
+            date(2026, 10, 5).and_hms_opt(9, 0, 0)
+        );
+    }
+
+    #[test]
+    fn walks_between_quays_before_the_first_trip() {
+        let tt = timetable();
+        let itineraries = tt.plan_journeys(&query("B1", "C", 0));
+        assert_eq!(itineraries.len(), 1);
+        assert_eq!(trips(&itineraries[0]), ["t2"]);
+    }
+
+    #[test]
+    fn keeps_to_max_transfers() {
+        let tt = timetable();
+        assert!(tt.plan_journeys(&query("A", "C", 0)).is_empty());
+    }
+}
@@ -31,6 +31,7 @@ pub struct Stop {
     // pub wheelchair_boarding: Availability,
     pub level_id: Option<String>,
     pub platform_code: Option<String>,
+    // INFO: transfers and pathways are kept in Timetable::transfers.
     // pub transfers: Vec<StopTransfer>,
     // pub pathways: Vec<Pathway>,
     pub tts_name: Option<String>,
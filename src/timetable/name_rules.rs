@@ -0,0 +1,242 @@
+use super::stop_search::normalize_name;
+use std::collections::{HashMap, HashSet};
+
+/// A way to pick the spelling all stops sharing a normalized name get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamePolicy {
+    /// The spelling with the most non ASCII characters, accents mostly.
+    Diacritics,
+    /// The spelling used by the most stop times.
+    MostFrequent,
+    /// The spelling of the station the stops belong to. The name of the
+    /// parent station is offered even when it is spelled differently from
+    /// all of its stops.
+    ParentStation,
+    /// The longest spelling in bytes.
+    Longest,
+}
+
+impl std::str::FromStr for NamePolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "diacritics" => Ok(Self::Diacritics),
+            "most-frequent" => Ok(Self::MostFrequent),
+            "parent-station" => Ok(Self::ParentStation),
+            "longest" => Ok(Self::Longest),
+            _ => Err(format!(
+                "unknown name policy `{s}`, expected `diacritics`, `most-frequent`, \
+                `parent-station` or `longest`"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for NamePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Diacritics => write!(f, "diacritics"),
+            Self::MostFrequent => write!(f, "most-frequent"),
+            Self::ParentStation => write!(f, "parent-station"),
+            Self::Longest => write!(f, "longest"),
+        }
+    }
+}
+
+/// How `uniformise_stop_names` picks stop names.
+#[derive(Debug, Clone)]
+pub struct NameRules {
+    /// Tried in order, each one only breaking the ties of the previous ones.
+    /// What's still tied goes to the first spelling in alphabetical order.
+    /// Whatever the policies, runs of whitespace are tidied to one space.
+    pub policies: Vec<NamePolicy>,
+    /// Names to use whatever the policies say, by stop id or by normalized
+    /// name.
+    pub overrides: HashMap<String, String>,
+}
+
+impl Default for NameRules {
+    fn default() -> Self {
+        Self {
+            // INFO: the longest spelling is most of the time the one that
+            // doesn't lack the diacritics.
+            policies: vec![NamePolicy::Longest],
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+impl NameRules {
+    /// Reads overrides from a RON map of stop ids or names to the name to
+    /// use, as in `{"Chelles Gare": "Gare de Chelles"}`.
+    pub fn load_overrides(&mut self, file_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let buf = std::fs::read_to_string(file_name)?;
+        let overrides: HashMap<String, String> = ron::from_str(&buf)?;
+        // INFO: keys can't be told apart from their looks, so each one is
+        // kept as a stop id and as a normalized name.
+        for (key, name) in overrides {
+            self.overrides.insert(normalize_name(&key), name.clone());
+            self.overrides.insert(key, name);
+        }
+        Ok(())
+    }
+
+    /// The override for a stop, looked up by id then by name.
+    pub(super) fn override_for(&self, stop_id: &str, normalized: &str) -> Option<&String> {
+        self.overrides
+            .get(stop_id)
+            .or_else(|| self.overrides.get(normalized))
+    }
+}
+
+/// What the policies know about a spelling.
+pub(super) struct Candidate<'a> {
+    pub name: &'a str,
+    /// Stop times using this spelling.
+    pub uses: usize,
+    /// Whether a station of the group, or the parent station of one of its
+    /// stops, is spelled this way.
+    pub is_station: bool,
+}
+
+impl NameRules {
+    /// Picks the spelling to keep, see `policies`.
+    pub(super) fn pick<'a, 'b>(
+        &self,
+        candidates: &'b [Candidate<'a>],
+    ) -> Option<&'b Candidate<'a>> {
+        candidates.iter().max_by(|a, b| {
+            self.policies
+                .iter()
+                .map(|policy| policy.score(a).cmp(&policy.score(b)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| b.name.cmp(a.name))
+        })
+    }
+
+    /// What made `picked` win over `other`, for the rename report.
+    pub(super) fn reason(&self, picked: &Candidate, other: &Candidate) -> String {
+        if picked.name == other.name {
+            return "whitespace".to_owned();
+        }
+        self.policies
+            .iter()
+            .find(|policy| policy.score(picked) != policy.score(other))
+            .map_or("alphabetical".to_owned(), |policy| policy.to_string())
+    }
+}
+
+impl NamePolicy {
+    fn score(self, candidate: &Candidate) -> usize {
+        match self {
+            Self::Diacritics => candidate.name.chars().filter(|c| !c.is_ascii()).count(),
+            Self::MostFrequent => candidate.uses,
+            Self::ParentStation => usize::from(candidate.is_station),
+            Self::Longest => candidate.name.len(),
+        }
+    }
+}
+
+/// A name change made by `uniformise_stop_names`.
+#[derive(Debug, Clone)]
+pub struct Rename {
+    pub stop_id: String,
+    pub from: String,
+    pub to: String,
+    /// What decided it: a policy, an override or whitespace tidying.
+    pub reason: String,
+}
+
+impl std::fmt::Display for Rename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\t{}\t{}\t{}",
+            self.stop_id, self.from, self.to, self.reason
+        )
+    }
+}
+
+/// Ids of the stations the stops belong to.
+pub(super) fn station_ids(stops: &HashMap<String, super::my_gtfs_structs::Stop>) -> HashSet<&str> {
+    stops
+        .values()
+        .filter_map(|stop| stop.parent_station.as_deref())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, uses: usize, is_station: bool) -> Candidate<'_> {
+        Candidate {
+            name,
+            uses,
+            is_station,
+        }
+    }
+
+    fn rules(policies: &[NamePolicy]) -> NameRules {
+        NameRules {
+            policies: policies.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn policies_break_ties_in_order() {
+        let candidates = [
+            candidate("Gare de Chelles", 10, false),
+            candidate("Gare de Chellès", 1, false),
+            candidate("GARE DE CHELLES", 1, true),
+        ];
+        let picked = |policies: &[NamePolicy]| rules(policies).pick(&candidates).unwrap().name;
+        assert_eq!(picked(&[NamePolicy::Diacritics]), "Gare de Chellès");
+        assert_eq!(picked(&[NamePolicy::MostFrequent]), "Gare de Chelles");
+        assert_eq!(picked(&[NamePolicy::ParentStation]), "GARE DE CHELLES");
+        // INFO: the accent takes two bytes in UTF-8.
+        assert_eq!(picked(&[NamePolicy::Longest]), "Gare de Chellès");
+        assert_eq!(picked(&[]), "GARE DE CHELLES");
+        assert_eq!(
+            picked(&[NamePolicy::ParentStation, NamePolicy::MostFrequent]),
+            "GARE DE CHELLES"
+        );
+
+        let rules = rules(&[NamePolicy::Longest, NamePolicy::MostFrequent]);
+        assert_eq!(rules.reason(&candidates[1], &candidates[0]), "longest");
+        assert_eq!(
+            rules.reason(&candidates[0], &candidates[2]),
+            "most-frequent"
+        );
+        assert_eq!(
+            NameRules::default().reason(&candidates[2], &candidates[0]),
+            "alphabetical"
+        );
+    }
+
+    #[test]
+    fn overrides_apply_by_id_and_by_name() {
+        let file_name = std::env::temp_dir().join(format!("overrides-{}.ron", std::process::id()));
+        std::fs::write(
+            &file_name,
+            r#"{"S1": "Torcy RER", "Chelles Gare": "Gare de Chelles"}"#,
+        )
+        .unwrap();
+        let mut rules = NameRules::default();
+        let loaded = rules.load_overrides(file_name.to_str().unwrap());
+        std::fs::remove_file(&file_name).unwrap();
+        loaded.unwrap();
+
+        let normalized = normalize_name("CHELLES  GARE");
+        assert_eq!(
+            rules.override_for("S2", &normalized).map(String::as_str),
+            Some("Gare de Chelles")
+        );
+        assert_eq!(
+            rules.override_for("S1", &normalized).map(String::as_str),
+            Some("Torcy RER")
+        );
+        assert_eq!(rules.override_for("S3", "torcy"), None);
+    }
+}
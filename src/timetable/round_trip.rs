@@ -0,0 +1,150 @@
+use super::departures::{Departure, DepartureQuery};
+use chrono::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+pub struct RoundTripQuery {
+    /// Ids of the stops the rider leaves from and comes back to.
+    pub origin: HashSet<String>,
+    /// Ids of the stops the rider goes to.
+    pub destination: HashSet<String>,
+    /// Window the outbound trip has to leave in.
+    pub leave_after: NaiveDateTime,
+    pub leave_before: NaiveDateTime,
+    /// Shortest time the rider wants to spend at the destination.
+    pub min_stay: chrono::Duration,
+}
+
+#[derive(Debug)]
+pub struct RoundTrip<'a> {
+    pub outbound: Departure<'a>,
+    pub inbound: Departure<'a>,
+    /// Time between arriving at the destination and leaving it.
+    pub stay: chrono::Duration,
+    /// Whether `inbound` is the last trip back of its service day.
+    pub is_last_return: bool,
+}
+
+impl super::Timetable {
+    /// Round trips leaving in the query window, in chronological order. Each
+    /// outbound trip comes with the earliest return allowing the minimum
+    /// stay, then with the last return of the day when it's a different one.
+    /// Outbound trips with no way back on the same service day are left out.
+    pub fn round_trips(&self, query: &RoundTripQuery) -> Vec<RoundTrip<'_>> {
+        let outbound_query = DepartureQuery {
+            from: query.origin.clone(),
+            towards: Some(query.destination.clone()),
+            direction: None,
+            at: query.leave_after,
+            count: 0,
+        };
+        let inbound_query = DepartureQuery {
+            from: query.destination.clone(),
+            towards: Some(query.origin.clone()),
+            direction: None,
+            at: query.leave_after,
+            count: 0,
+        };
+        let mut outbounds: Vec<_> = service_days(query.leave_after, query.leave_before)
+            .flat_map(|service_day| self.departures_on(service_day, &outbound_query))
+            .filter(|departure| {
+                (query.leave_after..=query.leave_before).contains(&departure.departs_at)
+            })
+            .collect();
+        outbounds.sort_by_key(|departure| departure.departs_at);
+
+        // INFO: returns are looked up once per service day rather than once
+        // per outbound trip.
+        let mut inbounds: HashMap<NaiveDate, Vec<Departure>> = HashMap::new();
+        let mut round_trips = vec![];
+        for outbound in outbounds {
+            let Some(arrives_at) = outbound.arrives_at else {
+                continue;
+            };
+            let returns = inbounds.entry(outbound.service_day).or_insert_with(|| {
+                let mut returns = self.departures_on(outbound.service_day, &inbound_query);
+                returns.sort_by_key(|departure| departure.departs_at);
+                returns
+            });
+            let earliest = arrives_at + query.min_stay;
+            let Some(first) = returns
+                .iter()
+                .find(|inbound| inbound.departs_at >= earliest)
+            else {
+                continue;
+            };
+            let last = returns.last().unwrap_or(first);
+            let is_same = first.departs_at == last.departs_at;
+            round_trips.push(RoundTrip {
+                outbound: outbound.clone(),
+                inbound: first.clone(),
+                stay: first.departs_at - arrives_at,
+                is_last_return: is_same,
+            });
+            if !is_same {
+                round_trips.push(RoundTrip {
+                    outbound,
+                    inbound: last.clone(),
+                    stay: last.departs_at - arrives_at,
+                    is_last_return: true,
+                });
+            }
+        }
+        round_trips
+    }
+}
+
+/// Service days whose trips may leave between `start` and `end`, starting
+/// the day before to catch trips running past midnight.
+fn service_days(start: NaiveDateTime, end: NaiveDateTime) -> impl Iterator<Item = NaiveDate> {
+    let first_day = start.date().pred_opt().unwrap_or(start.date());
+    first_day
+        .iter_days()
+        .take_while(move |service_day| *service_day <= end.date())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::Timetable;
+
+    #[test]
+    fn pairs_with_the_earliest_and_the_last_return() {
+        let mut tt = Timetable::new();
+        tt.add_service("W", WEEKDAYS, date(2024, 1, 1), date(2024, 1, 31));
+        tt.add_trip("o1", "W", "R", &[("H", "08:00"), ("T", "08:30")]);
+        tt.add_trip("o2", "W", "R", &[("H", "17:00"), ("T", "17:30")]);
+        tt.add_trip("o3", "W", "R", &[("H", "18:00"), ("T", "18:30")]);
+        tt.add_trip("i1", "W", "R", &[("T", "09:00"), ("H", "09:30")]);
+        tt.add_trip("i2", "W", "R", &[("T", "12:00"), ("H", "12:30")]);
+        tt.add_trip("i3", "W", "R", &[("T", "18:30"), ("H", "19:00")]);
+        let day = date(2024, 1, 8);
+        let round_trips = tt.round_trips(&RoundTripQuery {
+            origin: HashSet::from(["H".to_owned()]),
+            destination: HashSet::from(["T".to_owned()]),
+            leave_after: day.and_hms_opt(7, 0, 0).unwrap(),
+            leave_before: day.and_hms_opt(18, 0, 0).unwrap(),
+            min_stay: chrono::Duration::hours(1),
+        });
+        let pairs: Vec<_> = round_trips
+            .iter()
+            .map(|round_trip| {
+                (
+                    round_trip.outbound.trip.id.as_str(),
+                    round_trip.inbound.trip.id.as_str(),
+                    round_trip.stay.num_minutes(),
+                    round_trip.is_last_return,
+                )
+            })
+            .collect();
+        // INFO: i1 is too early after o1, o3 has no way back.
+        assert_eq!(
+            pairs,
+            [
+                ("o1", "i2", 210, false),
+                ("o1", "i3", 600, true),
+                ("o2", "i3", 60, true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use super::{simplify_ids, Direction};
+use std::collections::HashMap;
+
+/// Trips of a route serving the same stops in the same order. A route
+/// running both ways has at least two, short turns and extensions add more.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RoutePattern {
+    /// Derived from the route and the stops, so it stays the same from one
+    /// extraction to the next as long as the pattern does, and once the
+    /// timetable is written to a file.
+    pub id: String,
+    pub route_id: String,
+    /// First and last stop, as in "Chelles → Torcy".
+    pub label: String,
+    /// From the direction_id of the trips, or worked out from the order
+    /// of the stops when the feed leaves it out, see `derive_directions`.
+    pub direction: Option<Direction>,
+    pub stop_ids: Vec<String>,
+    pub trip_ids: Vec<String>,
+}
+
+impl RoutePattern {
+    pub fn trip_count(&self) -> usize {
+        self.trip_ids.len()
+    }
+}
+
+impl super::Timetable {
+    /// Clusters trips into route patterns by their ordered stops, sorted by
+    /// route then by decreasing number of trips. Has to be called again when
+    /// trips or stop names change.
+    pub fn build_route_patterns(&mut self) {
+        let mut patterns: HashMap<(&str, Vec<&str>), RoutePattern> = HashMap::new();
+        for (_, trip) in self.trips.iter() {
+            let stop_ids: Vec<&str> = trip
+                .stop_times
+                .iter()
+                .map(|stop_time| stop_time.stop_id.as_str())
+                .collect();
+            let pattern = patterns
+                .entry((&trip.route_id, stop_ids))
+                .or_insert_with_key(|(route_id, stop_ids)| RoutePattern {
+                    id: pattern_id(route_id, stop_ids),
+                    route_id: route_id.to_string(),
+                    label: format!(
+                        "{} → {}",
+                        trip.stop_times.first().map_or("?", |first| &first.name),
+                        trip.stop_times.last().map_or("?", |last| &last.name),
+                    ),
+                    direction: trip.direction,
+                    stop_ids: stop_ids.iter().map(|stop_id| stop_id.to_string()).collect(),
+                    trip_ids: vec![],
+                });
+            pattern.direction = pattern.direction.or(trip.direction);
+            pattern.trip_ids.push(trip.id.clone());
+        }
+        let mut patterns: Vec<_> = patterns.into_values().collect();
+        for pattern in &mut patterns {
+            pattern.trip_ids.sort();
+        }
+        patterns.sort_by(|a, b| {
+            (&a.route_id, b.trip_count(), &a.id).cmp(&(&b.route_id, a.trip_count(), &b.id))
+        });
+        derive_directions(&mut patterns);
+        self.route_patterns = patterns;
+    }
+
+    /// Direction of each trip by trip id: its own, or that of its pattern
+    /// when the feed leaves it out.
+    pub fn trip_directions(&self) -> HashMap<&str, Direction> {
+        let mut directions: HashMap<&str, Direction> = HashMap::new();
+        for pattern in &self.route_patterns {
+            if let Some(direction) = pattern.direction {
+                directions.extend(pattern.trip_ids.iter().map(|id| (id.as_str(), direction)));
+            }
+        }
+        for (_, trip) in self.trips.iter() {
+            if let Some(direction) = trip.direction {
+                directions.insert(&trip.id, direction);
+            }
+        }
+        directions
+    }
+}
+
+/// Gives a direction to the patterns of each route the feed leaves without
+/// one. The busiest pattern with a direction is the reference, or the
+/// busiest pattern taken as outbound when none has one. Patterns serving the
+/// stops they share with it in the same order go the same way, the others
+/// the opposite way. `patterns` are sorted as `build_route_patterns` does.
+fn derive_directions(patterns: &mut [RoutePattern]) {
+    for route in patterns.chunk_by_mut(|a, b| a.route_id == b.route_id) {
+        let reference = route
+            .iter()
+            .position(|pattern| pattern.direction.is_some())
+            .unwrap_or(0);
+        let direction = *route[reference]
+            .direction
+            .get_or_insert(Direction::Outbound);
+        let positions: HashMap<String, usize> = route[reference]
+            .stop_ids
+            .iter()
+            .enumerate()
+            .map(|(position, stop_id)| (stop_id.clone(), position))
+            .collect();
+        for pattern in route
+            .iter_mut()
+            .filter(|pattern| pattern.direction.is_none())
+        {
+            let along: Vec<usize> = pattern
+                .stop_ids
+                .iter()
+                .filter_map(|stop_id| positions.get(stop_id).copied())
+                .collect();
+            let forward = along.windows(2).filter(|pair| pair[0] < pair[1]).count();
+            let backward = along.windows(2).filter(|pair| pair[0] > pair[1]).count();
+            // INFO: a pattern sharing no stretch with the reference can't
+            // be placed.
+            pattern.direction = match forward.cmp(&backward) {
+                std::cmp::Ordering::Greater => Some(direction),
+                std::cmp::Ordering::Less => Some(direction.opposite()),
+                std::cmp::Ordering::Equal => None,
+            };
+        }
+    }
+}
+
+/// FNV-1a of the route and stop ids, std's hasher isn't guaranteed to give
+/// the same result from one release to the next. Ids are hashed as
+/// `to_file` writes them, for a pattern to keep its id once rebuilt from a
+/// file.
+fn pattern_id(route_id: &str, stop_ids: &[&str]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let mut hash = OFFSET_BASIS;
+    for id in std::iter::once(&route_id).chain(stop_ids) {
+        // INFO: the separator keeps ["ab", "c"] and ["a", "bc"] apart.
+        for byte in simplify_ids(id).bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    format!("{:08x}", (hash ^ (hash >> 32)) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::Timetable;
+
+    fn timetable() -> Timetable {
+        let mut tt = Timetable::new();
+        tt.add_service("IDFM:wk", WEEKDAYS, date(2026, 10, 1), date(2026, 12, 31));
+        for (id, name) in [
+            ("IDFM:A", "Alpha"),
+            ("IDFM:B", "Bravo"),
+            ("IDFM:C", "Charlie"),
+        ] {
+            tt.add_stop(id, name, None);
+        }
+        let (a, b, c) = ("IDFM:A", "IDFM:B", "IDFM:C");
+        tt.add_trip(
+            "t1",
+            "IDFM:wk",
+            "IDFM:r",
+            &[(a, "08:00"), (b, "08:10"), (c, "08:20")],
+        );
+        tt.add_trip(
+            "t2",
+            "IDFM:wk",
+            "IDFM:r",
+            &[(a, "09:00"), (b, "09:10"), (c, "09:20")],
+        );
+        tt.add_trip(
+            "t3",
+            "IDFM:wk",
+            "IDFM:r",
+            &[(c, "10:00"), (b, "10:10"), (a, "10:20")],
+        );
+        tt.add_trip("t4", "IDFM:wk", "IDFM:r", &[(a, "11:00"), (b, "11:10")]);
+        tt
+    }
+
+    fn directions(tt: &Timetable) -> Vec<(Vec<String>, Option<Direction>)> {
+        tt.route_patterns
+            .iter()
+            .map(|pattern| (pattern.trip_ids.clone(), pattern.direction))
+            .collect()
+    }
+
+    #[test]
+    fn ids_survive_a_round_trip() {
+        let mut tt = timetable();
+        tt.build_route_patterns();
+        let ids: Vec<_> = tt
+            .route_patterns
+            .iter()
+            .map(|pattern| pattern.id.clone())
+            .collect();
+        let serialized =
+            ron::ser::to_string_pretty(&tt, ron::ser::PrettyConfig::default()).unwrap();
+        let mut tt: Timetable = ron::from_str(&simplify_ids(&serialized)).unwrap();
+        assert_eq!(tt.route_patterns[0].route_id, "r");
+        tt.build_route_patterns();
+        let rebuilt: Vec<_> = tt
+            .route_patterns
+            .iter()
+            .map(|pattern| pattern.id.clone())
+            .collect();
+        assert_eq!(ids, rebuilt);
+    }
+
+    #[test]
+    fn directions_follow_the_busiest_pattern() {
+        let mut tt = timetable();
+        tt.build_route_patterns();
+        let trips = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            directions(&tt),
+            [
+                (trips(&["t1", "t2"]), Some(Direction::Outbound)),
+                (trips(&["t3"]), Some(Direction::Inbound)),
+                (trips(&["t4"]), Some(Direction::Outbound)),
+            ]
+        );
+    }
+
+    #[test]
+    fn directions_from_the_feed_come_first() {
+        let mut tt = timetable();
+        tt.trips.get_vec_mut("t3").unwrap()[0].direction = Some(Direction::Outbound);
+        tt.build_route_patterns();
+        let directions: Vec<_> = directions(&tt)
+            .into_iter()
+            .map(|(_, direction)| direction)
+            .collect();
+        assert_eq!(
+            directions,
+            [
+                Some(Direction::Inbound),
+                Some(Direction::Outbound),
+                Some(Direction::Inbound)
+            ]
+        );
+    }
+}
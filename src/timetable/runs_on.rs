@@ -0,0 +1,137 @@
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::sync::{PoisonError, RwLock};
+
+/// Whether services run on a given date, filled as queries come. It can be
+/// used from several threads at once and is not serialized.
+#[derive(Default)]
+pub struct ServiceDayCache(RwLock<HashMap<NaiveDate, HashMap<String, bool>>>);
+
+impl ServiceDayCache {
+    fn get(&self, service_id: &str, date: NaiveDate) -> Option<bool> {
+        // INFO: a panic while holding the lock can't leave a half written
+        // answer behind, so a poisoned cache is still good to use.
+        let cache = self.0.read().unwrap_or_else(PoisonError::into_inner);
+        cache.get(&date)?.get(service_id).copied()
+    }
+
+    fn insert(&self, service_id: &str, date: NaiveDate, runs: bool) {
+        let mut cache = self.0.write().unwrap_or_else(PoisonError::into_inner);
+        cache
+            .entry(date)
+            .or_default()
+            .insert(service_id.to_owned(), runs);
+    }
+}
+
+impl super::Timetable {
+    pub(super) fn runs_on_uncached(&self, service_id: &str, date: NaiveDate) -> bool {
+        match self.runs_by_exception(service_id, date) {
+            Some(super::my_gtfs_structs::Exception::Added) => true,
+            Some(super::my_gtfs_structs::Exception::Deleted) => false,
+            // INFO: services defined by calendar_dates.txt alone have no
+            // weekly pattern to fall back on, they only run on added dates.
+            None => self
+                .runs_on_interval_weekday(service_id, date)
+                .unwrap_or(false),
+        }
+    }
+
+    /// Whether the service is only defined by calendar dates, with no
+    /// calendar entry giving it a weekly pattern.
+    pub fn is_dates_only_service(&self, service_id: &str) -> bool {
+        !self.calendar.contains_key(service_id) && self.calendar_dates.contains_key(service_id)
+    }
+
+    /// Whether the service runs on `date`. The precomputed service calendar
+    /// answers when it can, otherwise answers are cached per date, so a single
+    /// timetable can be asked about any number of days.
+    pub fn runs_on(&self, service_id: &str, date: NaiveDate) -> bool {
+        if let Some(runs) = self.service_calendar.runs_on(service_id, date) {
+            return runs;
+        }
+        if let Some(runs) = self.service_day_cache.get(service_id, date) {
+            return runs;
+        }
+        let runs = self.runs_on_uncached(service_id, date);
+        self.service_day_cache.insert(service_id, date, runs);
+        runs
+    }
+
+    fn runs_by_exception(
+        &self,
+        service_id: &str,
+        date: NaiveDate,
+    ) -> Option<super::my_gtfs_structs::Exception> {
+        if let Some(exceptions) = self.calendar_dates.get(service_id) {
+            exceptions
+                .iter()
+                .filter(|exception| exception.date == date)
+                .map(|exception| exception.exception_type)
+                .fold(Option::None, |acc, excp_type| {
+                    if let Some(val) = acc {
+                        // INFO: if we have conflicting exceptions we act as if we don't have an
+                        // exception.
+                        if val != excp_type {
+                            eprintln!(
+                            "warning: conflicting exceptions detected (service_id: {service_id})"
+                        );
+                            return Option::None;
+                        }
+                    }
+                    Option::Some(excp_type)
+                })
+        } else {
+            None
+        }
+    }
+
+    fn runs_on_interval_weekday(&self, service_id: &str, date: NaiveDate) -> Option<bool> {
+        let gtfs_cal = self.calendar.get(service_id)?;
+        if date < gtfs_cal.start_date || date > gtfs_cal.end_date {
+            return None;
+        }
+        let runs_on_date = match date.weekday() {
+            chrono::Weekday::Mon if gtfs_cal.monday => true,
+            chrono::Weekday::Tue if gtfs_cal.tuesday => true,
+            chrono::Weekday::Wed if gtfs_cal.wednesday => true,
+            chrono::Weekday::Thu if gtfs_cal.thursday => true,
+            chrono::Weekday::Fri if gtfs_cal.friday => true,
+            chrono::Weekday::Sat if gtfs_cal.saturday => true,
+            chrono::Weekday::Sun if gtfs_cal.sunday => true,
+            _ => false,
+        };
+        Some(runs_on_date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::my_gtfs_structs::Exception;
+    use crate::timetable::Timetable;
+
+    #[test]
+    fn dates_only_services_run_on_their_added_dates() {
+        let mut tt = Timetable::new();
+        tt.add_service("wk", WEEKDAYS, date(2026, 10, 1), date(2026, 12, 31));
+        tt.add_exception("xmas", date(2026, 12, 24), Exception::Added);
+        tt.add_exception("xmas", date(2026, 12, 26), Exception::Added);
+        tt.add_exception("xmas", date(2026, 12, 25), Exception::Deleted);
+        // INFO: conflicting rows cancel out.
+        tt.add_exception("xmas", date(2026, 12, 28), Exception::Added);
+        tt.add_exception("xmas", date(2026, 12, 28), Exception::Deleted);
+        assert!(tt.is_dates_only_service("xmas"));
+        for built in [false, true] {
+            if built {
+                tt.build_service_calendar();
+            }
+            let running: Vec<_> = date(2026, 12, 20)
+                .iter_days()
+                .take(12)
+                .filter(|day| tt.runs_on("xmas", *day))
+                .collect();
+            assert_eq!(running, [date(2026, 12, 24), date(2026, 12, 26)]);
+        }
+    }
+}
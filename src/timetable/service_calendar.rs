@@ -0,0 +1,152 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// Every date each service runs on, resolved once from the calendar and
+/// calendar dates over the validity window of the feed, so that asking
+/// whether a service runs on a day is a lookup. Dates outside the window
+/// are left to `Timetable::runs_on` to work out.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ServiceCalendar {
+    /// First day of the window, `None` until the calendar is built.
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub services: HashMap<String, ActiveDates>,
+}
+
+/// One bit per day of the window, starting from its first day.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ActiveDates(Vec<u64>);
+
+impl ActiveDates {
+    fn with_days(days: usize) -> Self {
+        Self(vec![0; days.div_ceil(64)])
+    }
+
+    fn set(&mut self, day: usize) {
+        self.0[day / 64] |= 1 << (day % 64);
+    }
+
+    pub fn contains(&self, day: usize) -> bool {
+        self.0
+            .get(day / 64)
+            .is_some_and(|word| word & (1 << (day % 64)) != 0)
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+}
+
+impl ServiceCalendar {
+    /// Index of `date` in the window, `None` if it falls outside.
+    pub fn day(&self, date: NaiveDate) -> Option<usize> {
+        let (start_date, end_date) = (self.start_date?, self.end_date?);
+        if date < start_date || date > end_date {
+            return None;
+        }
+        usize::try_from((date - start_date).num_days()).ok()
+    }
+
+    /// Whether the service runs on `date`, `None` when the calendar can't
+    /// tell because the service or the date is unknown to it.
+    pub fn runs_on(&self, service_id: &str, date: NaiveDate) -> Option<bool> {
+        let day = self.day(date)?;
+        Some(self.services.get(service_id)?.contains(day))
+    }
+}
+
+impl super::Timetable {
+    /// Resolves every service into the set of dates it runs on, see
+    /// `ServiceCalendar`. Has to be called again when services change.
+    pub fn build_service_calendar(&mut self) {
+        let Some((start_date, end_date)) = self.validity_window() else {
+            self.service_calendar = ServiceCalendar::default();
+            return;
+        };
+        let days = usize::try_from((end_date - start_date).num_days() + 1).unwrap_or(0);
+        let service_ids: Vec<_> = self
+            .calendar
+            .keys()
+            .chain(self.calendar_dates.keys())
+            .cloned()
+            .collect();
+        let mut services = HashMap::new();
+        for service_id in service_ids {
+            if services.contains_key(&service_id) {
+                continue;
+            }
+            let mut active_dates = ActiveDates::with_days(days);
+            for (day, date) in start_date.iter_days().take(days).enumerate() {
+                if self.runs_on_uncached(&service_id, date) {
+                    active_dates.set(day);
+                }
+            }
+            services.insert(service_id, active_dates);
+        }
+        self.service_calendar = ServiceCalendar {
+            start_date: Some(start_date),
+            end_date: Some(end_date),
+            services,
+        };
+    }
+
+    /// The dates calendar.txt covers, or those calendar_dates.txt adds
+    /// services on for feeds without calendar.txt. A stray date, such as a
+    /// service removed years before, would otherwise stretch the window and
+    /// the bitmap of every service with it.
+    fn validity_window(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let mut windows = self
+            .calendar
+            .values()
+            .map(|calendar| (calendar.start_date, calendar.end_date))
+            .peekable();
+        if windows.peek().is_none() {
+            let added: Vec<_> = self
+                .calendar_dates
+                .values()
+                .flatten()
+                .filter(|date| date.exception_type == super::my_gtfs_structs::Exception::Added)
+                .map(|date| date.date)
+                .collect();
+            return Some((*added.iter().min()?, *added.iter().max()?));
+        }
+        windows
+            .reduce(|(start_a, end_a), (start_b, end_b)| (start_a.min(start_b), end_a.max(end_b)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::my_gtfs_structs::Exception;
+    use crate::timetable::Timetable;
+
+    #[test]
+    fn window_is_the_calendar_validity_range() {
+        let mut tt = Timetable::new();
+        tt.add_service("wk", WEEKDAYS, date(2026, 10, 1), date(2026, 12, 31));
+        tt.add_exception("wk", date(2020, 1, 6), Exception::Deleted);
+        tt.add_exception("wk", date(2027, 3, 1), Exception::Added);
+        tt.build_service_calendar();
+        let calendar = &tt.service_calendar;
+        assert_eq!(calendar.start_date, Some(date(2026, 10, 1)));
+        assert_eq!(calendar.end_date, Some(date(2026, 12, 31)));
+        assert_eq!(calendar.services["wk"].count(), 66);
+        // INFO: dates outside the window are still answered.
+        assert!(tt.runs_on("wk", date(2027, 3, 1)));
+        assert!(!tt.runs_on("wk", date(2027, 3, 2)));
+    }
+
+    #[test]
+    fn window_of_a_feed_without_calendar_spans_the_added_dates() {
+        let mut tt = Timetable::new();
+        tt.add_exception("xmas", date(2026, 12, 24), Exception::Added);
+        tt.add_exception("xmas", date(2026, 12, 31), Exception::Added);
+        tt.add_exception("xmas", date(2026, 1, 1), Exception::Deleted);
+        tt.build_service_calendar();
+        let calendar = &tt.service_calendar;
+        assert_eq!(calendar.start_date, Some(date(2026, 12, 24)));
+        assert_eq!(calendar.end_date, Some(date(2026, 12, 31)));
+        assert_eq!(calendar.services["xmas"].count(), 2);
+    }
+}
@@ -0,0 +1,72 @@
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Time of a stop relative to the start of its service day. GTFS lets it go
+/// past 24:00:00 for trips that run after midnight but belong to the service
+/// day before, so it can't be a `chrono::NaiveTime`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ServiceTime(u32);
+
+impl ServiceTime {
+    pub fn from_seconds(seconds: u32) -> Self {
+        Self(seconds)
+    }
+
+    /// Hours since the start of the service day, 25 for a `25:10:00`
+    /// departure.
+    pub fn hour(self) -> u32 {
+        self.0 / 3600
+    }
+
+    pub fn minute(self) -> u32 {
+        self.0 / 60 % 60
+    }
+
+    /// How many days after the service day the time falls on, 1 for a
+    /// `25:10:00` departure.
+    pub fn day_offset(self) -> u32 {
+        self.0 / SECONDS_PER_DAY
+    }
+
+    /// Actual date and time of this stop for a trip of `service_day`.
+    pub fn on(self, service_day: chrono::NaiveDate) -> chrono::NaiveDateTime {
+        service_day.and_time(chrono::NaiveTime::MIN) + chrono::Duration::seconds(self.0.into())
+    }
+}
+
+impl std::fmt::Display for ServiceTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (hours, minutes, seconds) = (self.hour(), self.minute(), self.0 % 60);
+        write!(f, "{hours:02}:{minutes:02}:{seconds:02}")
+    }
+}
+
+impl std::str::FromStr for ServiceTime {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':').map(|part| part.parse::<u32>());
+        let (Some(Ok(hours)), Some(Ok(minutes)), Some(Ok(seconds)), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err("service time is not formatted as HH:MM:SS");
+        };
+        if minutes >= 60 || seconds >= 60 {
+            return Err("service time has out of range minutes or seconds");
+        }
+        Ok(Self(hours * 3600 + minutes * 60 + seconds))
+    }
+}
+
+// INFO: stored as "HH:MM:SS" like GTFS does, which keeps the serialized
+// timetable as readable as it was with chrono::NaiveTime.
+impl serde::Serialize for ServiceTime {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ServiceTime {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
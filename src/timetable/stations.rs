@@ -0,0 +1,189 @@
+use super::my_gtfs_structs::Stop;
+use super::stop_search::normalize_name;
+use std::collections::{BTreeSet, HashMap};
+
+/// Furthest apart, in meters, two stops with the same name and no parent
+/// station can be and still be grouped into one station.
+const MAX_STATION_DISTANCE: f64 = 300.0;
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+/// Stops riders see as a single place: the platforms of a station, or both
+/// sides of a street for a bus stop.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Station {
+    /// The parent station id, or `cluster:` followed by the first stop id
+    /// for stations made by proximity.
+    pub id: String,
+    pub name: String,
+    pub stop_ids: Vec<String>,
+}
+
+impl super::Timetable {
+    /// Groups the stops trips serve into stations, see `group_stations`.
+    pub fn build_stations(&mut self) {
+        let served: BTreeSet<&str> = self
+            .trips
+            .iter()
+            .flat_map(|(_, trip)| &trip.stop_times)
+            .map(|stop_time| stop_time.stop_id.as_str())
+            .collect();
+        self.stations = group_stations(&self.stops, &served);
+    }
+}
+
+/// Groups the `served` stops into stations: by parent station when they have
+/// one, otherwise with the stops of the same name less than
+/// `MAX_STATION_DISTANCE` away.
+pub(super) fn group_stations(
+    stops: &HashMap<String, Stop>,
+    served: &BTreeSet<&str>,
+) -> Vec<Station> {
+    let mut by_parent: HashMap<&str, Vec<&Stop>> = HashMap::new();
+    let mut by_name: HashMap<String, Vec<&Stop>> = HashMap::new();
+    let mut nameless: Vec<&Stop> = vec![];
+    for stop in served.iter().filter_map(|stop_id| stops.get(*stop_id)) {
+        match (&stop.parent_station, &stop.name) {
+            (Some(parent), _) => by_parent.entry(parent).or_default().push(stop),
+            (None, Some(name)) => by_name.entry(normalize_name(name)).or_default().push(stop),
+            (None, None) => nameless.push(stop),
+        }
+    }
+
+    let mut stations: Vec<Station> = by_parent
+        .into_iter()
+        .map(|(parent, children)| {
+            let name = stops.get(parent).and_then(|station| station.name.clone());
+            station(parent.to_owned(), name, &children)
+        })
+        .collect();
+    for stops in by_name.into_values() {
+        stations.extend(
+            cluster(stops)
+                .into_iter()
+                .map(|stops| station(format!("cluster:{}", stops[0].id), None, &stops)),
+        );
+    }
+    // INFO: with no name to go by, nameless stops are stations of their own.
+    stations.extend(
+        nameless
+            .into_iter()
+            .map(|stop| station(format!("cluster:{}", stop.id), None, &[stop])),
+    );
+    stations.sort_by(|a, b| (&a.name, &a.id).cmp(&(&b.name, &b.id)));
+    stations
+}
+
+fn station(id: String, name: Option<String>, stops: &[&Stop]) -> Station {
+    // INFO: without a parent station to name it, a station gets the name
+    // most of its stops have.
+    let name = name.unwrap_or_else(|| {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for name in stops.iter().filter_map(|stop| stop.name.as_deref()) {
+            *counts.entry(name).or_default() += 1;
+        }
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map_or(id.clone(), |(name, _)| name.to_owned())
+    });
+    let mut stop_ids: Vec<_> = stops.iter().map(|stop| stop.id.clone()).collect();
+    stop_ids.sort();
+    Station { id, name, stop_ids }
+}
+
+/// Splits stops sharing a name into groups of stops close to one another.
+/// Stops without coordinates can't be placed, they make stations of their
+/// own.
+fn cluster(mut stops: Vec<&Stop>) -> Vec<Vec<&Stop>> {
+    stops.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut clusters: Vec<Vec<&Stop>> = vec![];
+    for stop in stops {
+        let (near, mut far): (Vec<_>, Vec<_>) = clusters.into_iter().partition(|cluster| {
+            cluster.iter().any(|other| {
+                distance(stop, other).is_some_and(|distance| distance <= MAX_STATION_DISTANCE)
+            })
+        });
+        // INFO: a stop close to several clusters joins them into one.
+        let mut joined: Vec<_> = near.into_iter().flatten().collect();
+        joined.push(stop);
+        joined.sort_by(|a, b| a.id.cmp(&b.id));
+        far.push(joined);
+        clusters = far;
+    }
+    clusters
+}
+
+/// Great circle distance in meters between two stops, `None` if one of them
+/// has no coordinates.
+fn distance(a: &Stop, b: &Stop) -> Option<f64> {
+    let (a_latitude, a_longitude) = (a.latitude?.to_radians(), a.longitude?.to_radians());
+    let (b_latitude, b_longitude) = (b.latitude?.to_radians(), b.longitude?.to_radians());
+    let haversine = ((b_latitude - a_latitude) / 2.0).sin().powi(2)
+        + a_latitude.cos() * b_latitude.cos() * ((b_longitude - a_longitude) / 2.0).sin().powi(2);
+    Some(2.0 * EARTH_RADIUS * haversine.sqrt().asin())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::Timetable;
+
+    /// One thousandth of a degree of latitude is about 111 meters.
+    const MILLIDEGREE: f64 = 0.001;
+
+    fn place(tt: &mut Timetable, id: &str, name: &str, latitude: Option<f64>) {
+        let stop = tt.add_stop(id, name, None);
+        stop.latitude = latitude;
+        stop.longitude = latitude.map(|_| 2.35);
+    }
+
+    #[test]
+    fn measures_great_circle_distances() {
+        let mut tt = Timetable::new();
+        place(&mut tt, "A", "Mairie", Some(48.85));
+        place(&mut tt, "B", "Mairie", Some(48.85 + MILLIDEGREE));
+        place(&mut tt, "C", "Mairie", None);
+        let distance_to = |id: &str| distance(&tt.stops["A"], &tt.stops[id]);
+        assert!(distance_to("A") == Some(0.0));
+        assert!(distance_to("B").is_some_and(|meters| (meters - 111.2).abs() < 0.1));
+        assert!(distance_to("C").is_none());
+    }
+
+    #[test]
+    fn groups_close_stops_of_the_same_name() {
+        let mut tt = Timetable::new();
+        place(&mut tt, "A", "Mairie", Some(48.85));
+        place(&mut tt, "B", "MAIRIE", Some(48.85 + 2.0 * MILLIDEGREE));
+        // INFO: C is too far from A, but close enough to B.
+        place(&mut tt, "C", "Mairie", Some(48.85 + 4.0 * MILLIDEGREE));
+        place(&mut tt, "D", "Mairie", Some(48.85 + 20.0 * MILLIDEGREE));
+        place(&mut tt, "E", "Mairie", None);
+        place(&mut tt, "F", "Poste", Some(48.85));
+        tt.add_stop("G1", "Gare quai 1", Some("G"));
+        tt.add_stop("G2", "Gare quai 2", Some("G"));
+        tt.add_stop("G", "Gare", None);
+        // INFO: a stop id alike a normalized name.
+        tt.add_stop("mairie", "", None).name = None;
+        let served = ["A", "B", "C", "D", "E", "F", "G1", "G2", "mairie"]
+            .into_iter()
+            .collect();
+        let stations: Vec<_> = group_stations(&tt.stops, &served)
+            .into_iter()
+            .map(|station| (station.id, station.name, station.stop_ids.join(" ")))
+            .collect();
+        let station = |id: &str, name: &str, stop_ids: &str| {
+            (id.to_owned(), name.to_owned(), stop_ids.to_owned())
+        };
+        assert_eq!(
+            stations,
+            [
+                station("G", "Gare", "G1 G2"),
+                station("cluster:A", "Mairie", "A B C"),
+                station("cluster:D", "Mairie", "D"),
+                station("cluster:E", "Mairie", "E"),
+                station("cluster:F", "Poste", "F"),
+                station("cluster:mairie", "cluster:mairie", "mairie"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use super::stop_search::normalize_name;
+use std::collections::HashSet;
+
+impl super::Timetable {
+    /// Resolves stops given by stop id, station id, station name or stop
+    /// name, ignoring case and diacritics, to the ids of the matching stops.
+    /// A station stands for all of its stops, see `build_stations`. Stop
+    /// names are only looked at when no station has that name, for quays
+    /// named apart from their station.
+    pub fn resolve_stops(&self, queries: &[String]) -> Result<HashSet<String>, String> {
+        let mut stop_ids = HashSet::new();
+        for query in queries {
+            if let Some(station) = self.stations.iter().find(|station| station.id == *query) {
+                stop_ids.extend(station.stop_ids.iter().cloned());
+                continue;
+            }
+            if self.stops.contains_key(query) {
+                stop_ids.insert(query.clone());
+                continue;
+            }
+            let normalized = normalize_name(query);
+            let matching: Vec<_> = self
+                .stations
+                .iter()
+                .filter(|station| normalize_name(&station.name) == normalized)
+                .collect();
+            match matching.as_slice() {
+                [] => {
+                    let named: Vec<_> = self
+                        .stops
+                        .values()
+                        .filter(|stop| {
+                            stop.name
+                                .as_ref()
+                                .is_some_and(|name| normalize_name(name) == normalized)
+                        })
+                        .map(|stop| stop.id.clone())
+                        .collect();
+                    if named.is_empty() {
+                        return Err(self.no_match(query));
+                    }
+                    stop_ids.extend(named);
+                }
+                [station] => stop_ids.extend(station.stop_ids.iter().cloned()),
+                // INFO: stations sharing a name are far apart, often in
+                // different towns, better ask than mix them up.
+                _ => {
+                    let candidates: Vec<_> = matching
+                        .iter()
+                        .map(|station| format!("{} ({} stops)", station.id, station.stop_ids.len()))
+                        .collect();
+                    return Err(format!(
+                        "{query} names several stations, use one of their ids: {}",
+                        candidates.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(stop_ids)
+    }
+
+    fn no_match(&self, query: &str) -> String {
+        let mut suggestions: Vec<_> = self
+            .search_stops(query)
+            .into_iter()
+            .map(|found| found.name)
+            .collect();
+        // INFO: the platforms of a station share its name.
+        suggestions.dedup();
+        suggestions.truncate(3);
+        if suggestions.is_empty() {
+            return format!("no stop matches {query}");
+        }
+        format!(
+            "no stop matches {query}, did you mean {}?",
+            suggestions.join(", ")
+        )
+    }
+}
@@ -0,0 +1,172 @@
+/// How well a stop name matches a search, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchQuality {
+    /// Same name once normalized.
+    Exact,
+    /// The name starts with the search.
+    Prefix,
+    /// Every word of the search starts a word of the name, in any order.
+    Words,
+    /// Every word of the search is a few typos away from a word of the name,
+    /// with this many typos in total.
+    Fuzzy(usize),
+}
+
+#[derive(Debug)]
+pub struct StopMatch<'a> {
+    pub stop_id: &'a str,
+    pub name: &'a str,
+    pub quality: MatchQuality,
+}
+
+/// Form of a name stop names are compared in: without diacritics, in lower
+/// case, with single spaces between words.
+pub fn normalize_name(name: &str) -> String {
+    let name = unidecode::unidecode(name).to_lowercase();
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Words of a normalized name, punctuation such as the hyphen of
+/// "Noisy-Champs" being a separator.
+fn words(normalized: &str) -> Vec<&str> {
+    normalized
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+impl super::Timetable {
+    /// Stops whose name matches `query`, best matches first, then by name.
+    pub fn search_stops(&self, query: &str) -> Vec<StopMatch<'_>> {
+        let query = normalize_name(query);
+        let query_words = words(&query);
+        if query_words.is_empty() {
+            return vec![];
+        }
+        let mut matches: Vec<_> = self
+            .stops
+            .values()
+            .filter_map(|stop| {
+                let name = stop.name.as_deref()?;
+                let quality = match_quality(&query_words, &normalize_name(name))?;
+                Some(StopMatch {
+                    stop_id: &stop.id,
+                    name,
+                    quality,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| (a.quality, a.name, a.stop_id).cmp(&(b.quality, b.name, b.stop_id)));
+        matches
+    }
+}
+
+fn match_quality(query_words: &[&str], name: &str) -> Option<MatchQuality> {
+    let name_words = words(name);
+    let (query, name) = (query_words.join(" "), name_words.join(" "));
+    if query == name {
+        return Some(MatchQuality::Exact);
+    }
+    if name.starts_with(&query) {
+        return Some(MatchQuality::Prefix);
+    }
+    let starts_a_word =
+        |query_word: &str| name_words.iter().any(|word| word.starts_with(query_word));
+    if query_words
+        .iter()
+        .all(|query_word| starts_a_word(query_word))
+    {
+        return Some(MatchQuality::Words);
+    }
+    let mut typos = 0;
+    for query_word in query_words {
+        // INFO: short words are often articles, and one typo in them makes
+        // another word, so they have to be spelled right.
+        let allowed = match query_word.chars().count() {
+            0..=3 => 0,
+            length => (length + 1) / 3,
+        };
+        typos += name_words
+            .iter()
+            .map(|word| edit_distance(query_word, word))
+            .filter(|distance| *distance <= allowed)
+            .min()?;
+    }
+    Some(MatchQuality::Fuzzy(typos))
+}
+
+/// Edit distance counting the characters to insert, remove or replace and
+/// the swaps of two neighbouring characters to go from `a` to `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // INFO: distances between the first i characters of `a` and the first j
+    // of `b`, for the last three values of i.
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let replace = previous[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            current[j] = replace.min(previous[j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::Timetable;
+
+    #[test]
+    fn counts_swaps_as_one_typo() {
+        assert_eq!(edit_distance("gare", "gare"), 0);
+        assert_eq!(edit_distance("", "gare"), 4);
+        assert_eq!(edit_distance("grae", "gare"), 1);
+        assert_eq!(edit_distance("chelle", "chelles"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        // INFO: a swapped pair isn't edited again, unlike with the full
+        // Damerau-Levenshtein distance, which gives 2.
+        assert_eq!(edit_distance("ca", "abc"), 3);
+    }
+
+    #[test]
+    fn ranks_matches_from_exact_to_fuzzy() {
+        let mut tt = Timetable::new();
+        tt.add_stop("1", "Chelles", None);
+        tt.add_stop("2", "Chelles Centre", None);
+        tt.add_stop("3", "Gare de Chelles", None);
+        tt.add_stop("4", "Noisy-Champs", None);
+        tt.add_stop("5", "Champs-sur-Marne", None);
+        let search = |query| -> Vec<_> {
+            tt.search_stops(query)
+                .into_iter()
+                .map(|found| (found.name, found.quality))
+                .collect()
+        };
+        assert_eq!(
+            search("CHELLES"),
+            [
+                ("Chelles", MatchQuality::Exact),
+                ("Chelles Centre", MatchQuality::Prefix),
+                ("Gare de Chelles", MatchQuality::Words),
+            ]
+        );
+        assert_eq!(
+            search("champs noisy"),
+            [("Noisy-Champs", MatchQuality::Words)]
+        );
+        assert_eq!(
+            search("gare de chleles"),
+            [("Gare de Chelles", MatchQuality::Fuzzy(1))]
+        );
+        // INFO: too many typos for a word this short.
+        assert!(search("chmaps sru").is_empty());
+    }
+}
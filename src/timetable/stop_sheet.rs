@@ -0,0 +1,632 @@
+use super::my_gtfs_structs::Exception;
+use super::service_time::ServiceTime;
+use super::{Direction, Trip};
+use chrono::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+
+/// Most dates listed in a footnote before giving a count instead.
+const MAX_LISTED_DATES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DayType {
+    Weekday,
+    Saturday,
+    /// Holidays run the Sunday service, added through calendar dates, see
+    /// `holidays`.
+    Sunday,
+}
+
+impl DayType {
+    const ALL: [DayType; 3] = [Self::Weekday, Self::Saturday, Self::Sunday];
+
+    fn of(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Sat => Self::Saturday,
+            Weekday::Sun => Self::Sunday,
+            _ => Self::Weekday,
+        }
+    }
+
+    fn of_date(date: NaiveDate, holidays: &HashSet<NaiveDate>) -> Self {
+        if holidays.contains(&date) {
+            Self::Sunday
+        } else {
+            Self::of(date.weekday())
+        }
+    }
+}
+
+impl std::fmt::Display for DayType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Weekday => write!(f, "Monday to Friday"),
+            Self::Saturday => write!(f, "Saturday"),
+            Self::Sunday => write!(f, "Sunday and holidays"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SheetFormat {
+    Text,
+    Html,
+    Markdown,
+}
+
+impl std::str::FromStr for SheetFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "html" => Ok(Self::Html),
+            "markdown" | "md" => Ok(Self::Markdown),
+            _ => Err(format!(
+                "unknown sheet format `{s}`, expected `text`, `html` or `markdown`"
+            )),
+        }
+    }
+}
+
+/// What goes on a sheet: departures from `stops` from the `from` date on,
+/// optionally only those of trips going one way or following one route
+/// pattern.
+pub struct SheetQuery {
+    pub stops: HashSet<String>,
+    pub from: NaiveDate,
+    pub direction: Option<Direction>,
+    pub pattern_id: Option<String>,
+}
+
+/// Departure at a stop on a sheet, with the footnote telling when it runs
+/// if its service doesn't run on every day of its column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SheetEntry {
+    pub time: ServiceTime,
+    pub footnote: Option<usize>,
+}
+
+/// A classic bus stop timetable: one column per day type, one row per hour,
+/// minutes in the cells.
+#[derive(Debug)]
+pub struct StopSheet {
+    pub stop_name: String,
+    pub headsigns: Vec<String>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_until: Option<NaiveDate>,
+    pub columns: BTreeMap<DayType, BTreeSet<SheetEntry>>,
+    pub footnotes: Vec<String>,
+}
+
+impl super::Timetable {
+    /// Builds the sheet of the trips leaving `query.stops`, placed in day
+    /// type columns by the weekdays their service runs on. The sheet is valid
+    /// from `query.from` to the end of the timetable, services that don't run
+    /// in between are left out.
+    pub fn stop_sheet(&self, query: &SheetQuery) -> StopSheet {
+        let pattern = query.pattern_id.as_ref().and_then(|pattern_id| {
+            self.route_patterns
+                .iter()
+                .find(|pattern| pattern.id == *pattern_id)
+        });
+        let mut sheet = StopSheet {
+            stop_name: query
+                .stops
+                .iter()
+                .filter_map(|stop_id| self.stops.get(stop_id)?.name.clone())
+                .min()
+                .unwrap_or_default(),
+            headsigns: vec![],
+            valid_from: self
+                .service_calendar
+                .start_date
+                .map(|start_date| start_date.max(query.from))
+                .filter(|valid_from| Some(*valid_from) <= self.service_calendar.end_date),
+            valid_until: self.service_calendar.end_date,
+            columns: DayType::ALL
+                .into_iter()
+                .map(|day_type| (day_type, BTreeSet::new()))
+                .collect(),
+            footnotes: vec![],
+        };
+        let window = sheet.valid_from.zip(sheet.valid_until);
+        let directions = self.trip_directions();
+        let holidays = self.holidays();
+        let mut runs_within: HashMap<&str, bool> = HashMap::new();
+        let mut headsigns = BTreeSet::new();
+        // INFO: going down the sheet, so footnotes are lettered in the order
+        // riders read them.
+        let mut departures: Vec<_> = self
+            .trips
+            .iter()
+            .filter_map(|(_, trip)| Some((departure_from(trip, &query.stops)?, trip)))
+            .collect();
+        departures.sort_by(|(a_time, a), (b_time, b)| (a_time, &a.id).cmp(&(b_time, &b.id)));
+        for (time, trip) in departures {
+            if query.direction.is_some()
+                && directions.get(trip.id.as_str()).copied() != query.direction
+            {
+                continue;
+            }
+            if pattern.is_some_and(|pattern| !pattern.trip_ids.contains(&trip.id)) {
+                continue;
+            }
+            let Some((valid_from, valid_until)) = window else {
+                continue;
+            };
+            let runs = *runs_within.entry(&trip.service_id).or_insert_with(|| {
+                valid_from
+                    .iter_days()
+                    .take_while(|date| *date <= valid_until)
+                    .any(|date| self.runs_on(&trip.service_id, date))
+            });
+            if !runs {
+                continue;
+            }
+            let footnote = self
+                .service_footnote(&trip.service_id, valid_from, valid_until)
+                .map(|footnote| {
+                    match sheet.footnotes.iter().position(|known| *known == footnote) {
+                        Some(index) => index,
+                        None => {
+                            sheet.footnotes.push(footnote);
+                            sheet.footnotes.len() - 1
+                        }
+                    }
+                });
+            let day_types = self.service_day_types(&trip.service_id, &holidays);
+            for day_type in &day_types {
+                if let Some(column) = sheet.columns.get_mut(day_type) {
+                    column.insert(SheetEntry { time, footnote });
+                }
+            }
+            if !day_types.is_empty() {
+                headsigns.insert(trip.headsign().to_owned());
+            }
+        }
+        sheet.headsigns = headsigns.into_iter().collect();
+        sheet
+    }
+
+    /// Day types a service runs on: from the weekdays of its calendar, or
+    /// from the dates it is added on when it has no calendar.
+    fn service_day_types(
+        &self,
+        service_id: &str,
+        holidays: &HashSet<NaiveDate>,
+    ) -> BTreeSet<DayType> {
+        match self.calendar.get(service_id) {
+            Some(calendar) => calendar_weekdays(calendar)
+                .into_iter()
+                .map(DayType::of)
+                .collect(),
+            None => self
+                .calendar_dates
+                .get(service_id)
+                .into_iter()
+                .flatten()
+                .filter(|date| date.exception_type == Exception::Added)
+                .map(|date| DayType::of_date(date.date, holidays))
+                .collect(),
+        }
+    }
+
+    /// Dates on which a service is added while one whose calendar runs that
+    /// weekday is removed. Feeds don't flag holidays, this is how they show.
+    fn holidays(&self) -> HashSet<NaiveDate> {
+        let exceptions = |exception_type| {
+            self.calendar_dates
+                .iter()
+                .flat_map(|(service_id, dates)| dates.iter().map(move |date| (service_id, date)))
+                .filter(move |(_, date)| date.exception_type == exception_type)
+        };
+        let removed: HashSet<_> = exceptions(Exception::Deleted)
+            .filter(|(service_id, date)| {
+                self.calendar.get(*service_id).is_some_and(|calendar| {
+                    calendar_weekdays(calendar).contains(&date.date.weekday())
+                })
+            })
+            .map(|(_, date)| date.date)
+            .collect();
+        exceptions(Exception::Added)
+            .map(|(_, date)| date.date)
+            .filter(|date| date.weekday() != Weekday::Sun && removed.contains(date))
+            .collect()
+    }
+
+    /// What riders need to know about a service that doesn't run on every
+    /// day of its day types between `valid_from` and `valid_until`, `None`
+    /// when there is nothing to tell.
+    fn service_footnote(
+        &self,
+        service_id: &str,
+        valid_from: NaiveDate,
+        valid_until: NaiveDate,
+    ) -> Option<String> {
+        let dates_of = |exception_type| -> Vec<NaiveDate> {
+            let mut dates: Vec<_> = self
+                .calendar_dates
+                .get(service_id)
+                .into_iter()
+                .flatten()
+                .filter(|date| date.exception_type == exception_type)
+                .map(|date| date.date)
+                .filter(|date| (valid_from..=valid_until).contains(date))
+                .collect();
+            dates.sort();
+            dates.dedup();
+            dates
+        };
+        let added = dates_of(Exception::Added);
+        let deleted = dates_of(Exception::Deleted);
+        let mut notes = vec![];
+        match self.calendar.get(service_id) {
+            Some(calendar) => {
+                // INFO: school term, holiday and summer services sit side by
+                // side with the year round ones.
+                let starts_later = calendar.start_date > valid_from;
+                let ends_earlier = calendar.end_date < valid_until;
+                let format = |date: NaiveDate| date.format("%Y-%m-%d");
+                match (starts_later, ends_earlier) {
+                    (true, true) => notes.push(format!(
+                        "from {} to {}",
+                        format(calendar.start_date),
+                        format(calendar.end_date)
+                    )),
+                    (true, false) => notes.push(format!("from {}", format(calendar.start_date))),
+                    (false, true) => notes.push(format!("until {}", format(calendar.end_date))),
+                    (false, false) => {}
+                }
+                let weekdays = calendar_weekdays(calendar);
+                let weekday_count = weekdays
+                    .iter()
+                    .filter(|weekday| DayType::of(**weekday) == DayType::Weekday)
+                    .count();
+                if (1..5).contains(&weekday_count) {
+                    let names: Vec<_> =
+                        weekdays.iter().map(|weekday| weekday.to_string()).collect();
+                    notes.push(format!("only on {}", names.join(", ")));
+                }
+                if !added.is_empty() {
+                    notes.push(format!("also on {}", list_dates(&added)));
+                }
+            }
+            None if !added.is_empty() => notes.push(format!("only on {}", list_dates(&added))),
+            None => {}
+        }
+        if !deleted.is_empty() {
+            notes.push(format!("not on {}", list_dates(&deleted)));
+        }
+        if notes.is_empty() {
+            None
+        } else {
+            Some(notes.join("; "))
+        }
+    }
+}
+
+impl StopSheet {
+    pub fn render(&self, format: SheetFormat) -> String {
+        match format {
+            SheetFormat::Text => self.to_text(),
+            SheetFormat::Html => self.to_html(),
+            SheetFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    fn title(&self) -> String {
+        if self.headsigns.is_empty() {
+            self.stop_name.clone()
+        } else {
+            format!("{}, towards {}", self.stop_name, self.headsigns.join(" / "))
+        }
+    }
+
+    fn validity(&self) -> Option<String> {
+        Some(format!(
+            "Valid from {} to {}",
+            self.valid_from?, self.valid_until?
+        ))
+    }
+
+    /// Hours with at least one departure, along with the minutes of each
+    /// column, as "07 22a 37".
+    fn rows(&self) -> Vec<(u32, Vec<String>)> {
+        let hours: BTreeSet<_> = self
+            .columns
+            .values()
+            .flatten()
+            .map(|entry| entry.time.hour())
+            .collect();
+        hours
+            .into_iter()
+            .map(|hour| {
+                let cells = self
+                    .columns
+                    .values()
+                    .map(|column| {
+                        let minutes: Vec<_> = column
+                            .iter()
+                            .filter(|entry| entry.time.hour() == hour)
+                            .map(|entry| {
+                                let mark = entry.footnote.map(footnote_mark).unwrap_or_default();
+                                format!("{:02}{mark}", entry.time.minute())
+                            })
+                            .collect();
+                        minutes.join(" ")
+                    })
+                    .collect();
+                (hour, cells)
+            })
+            .collect()
+    }
+
+    fn to_text(&self) -> String {
+        let headers: Vec<_> = self
+            .columns
+            .keys()
+            .map(|day_type| day_type.to_string())
+            .collect();
+        let rows = self.rows();
+        let widths: Vec<_> = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                rows.iter()
+                    .map(|(_, cells)| cells[index].chars().count())
+                    .chain([header.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", self.title());
+        if let Some(validity) = self.validity() {
+            let _ = writeln!(text, "{validity}");
+        }
+        text.push('\n');
+        let line = |hour: &str, cells: &[String]| {
+            let cells: Vec<_> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            format!("{hour:>4} | {}", cells.join(" | "))
+                .trim_end()
+                .to_owned()
+        };
+        let _ = writeln!(text, "{}", line("", &headers));
+        for (hour, cells) in &rows {
+            let _ = writeln!(text, "{}", line(&format!("{:02}", hour % 24), cells));
+        }
+        for (index, footnote) in self.footnotes.iter().enumerate() {
+            let _ = writeln!(text, "{}: {footnote}", footnote_mark(index));
+        }
+        text
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "## {}\n", self.title());
+        if let Some(validity) = self.validity() {
+            let _ = writeln!(text, "{validity}\n");
+        }
+        let headers: Vec<_> = self
+            .columns
+            .keys()
+            .map(|day_type| day_type.to_string())
+            .collect();
+        let _ = writeln!(text, "| Hour | {} |", headers.join(" | "));
+        let _ = writeln!(text, "| ---: |{}", " --- |".repeat(headers.len()));
+        for (hour, cells) in self.rows() {
+            let _ = writeln!(text, "| {:02} | {} |", hour % 24, cells.join(" | "));
+        }
+        if !self.footnotes.is_empty() {
+            text.push('\n');
+        }
+        for (index, footnote) in self.footnotes.iter().enumerate() {
+            let _ = writeln!(text, "- **{}**: {footnote}", footnote_mark(index));
+        }
+        text
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = writeln!(html, "<section class=\"stop-sheet\">");
+        let _ = writeln!(html, "<h2>{}</h2>", escape_html(&self.title()));
+        if let Some(validity) = self.validity() {
+            let _ = writeln!(html, "<p>{validity}</p>");
+        }
+        let _ = writeln!(html, "<table>");
+        let _ = write!(html, "<tr><th>Hour</th>");
+        for day_type in self.columns.keys() {
+            let _ = write!(html, "<th>{day_type}</th>");
+        }
+        let _ = writeln!(html, "</tr>");
+        for (hour, cells) in self.rows() {
+            let _ = write!(html, "<tr><th>{:02}</th>", hour % 24);
+            for cell in cells {
+                let _ = write!(html, "<td>{cell}</td>");
+            }
+            let _ = writeln!(html, "</tr>");
+        }
+        let _ = writeln!(html, "</table>");
+        if !self.footnotes.is_empty() {
+            let _ = writeln!(html, "<ul>");
+            for (index, footnote) in self.footnotes.iter().enumerate() {
+                let _ = writeln!(
+                    html,
+                    "<li><b>{}</b>: {}</li>",
+                    footnote_mark(index),
+                    escape_html(footnote)
+                );
+            }
+            let _ = writeln!(html, "</ul>");
+        }
+        let _ = writeln!(html, "</section>");
+        html
+    }
+}
+
+/// Time the trip leaves the first of `stops` it serves. Trips ending there
+/// aren't departures.
+fn departure_from(trip: &Trip, stops: &HashSet<String>) -> Option<ServiceTime> {
+    trip.stop_times[..trip.stop_times.len().saturating_sub(1)]
+        .iter()
+        .find(|stop_time| stops.contains(&stop_time.stop_id))
+        .map(|stop_time| stop_time.departure)
+}
+
+fn calendar_weekdays(calendar: &super::my_gtfs_structs::Calendar) -> Vec<Weekday> {
+    [
+        (calendar.monday, Weekday::Mon),
+        (calendar.tuesday, Weekday::Tue),
+        (calendar.wednesday, Weekday::Wed),
+        (calendar.thursday, Weekday::Thu),
+        (calendar.friday, Weekday::Fri),
+        (calendar.saturday, Weekday::Sat),
+        (calendar.sunday, Weekday::Sun),
+    ]
+    .into_iter()
+    .filter(|(runs, _)| *runs)
+    .map(|(_, weekday)| weekday)
+    .collect()
+}
+
+fn list_dates(dates: &[NaiveDate]) -> String {
+    let listed: Vec<_> = dates
+        .iter()
+        .take(MAX_LISTED_DATES)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .collect();
+    match dates.len().checked_sub(MAX_LISTED_DATES) {
+        Some(more) if more > 0 => format!("{} and {more} more dates", listed.join(", ")),
+        _ => listed.join(", "),
+    }
+}
+
+/// "a" for the first footnote, "z" for the 26th, "aa" for the 27th.
+fn footnote_mark(index: usize) -> String {
+    let letter = char::from(b'a' + (index % 26) as u8);
+    letter.to_string().repeat(index / 26 + 1)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::Timetable;
+
+    /// Weekday and Sunday services with New Year's Day run as a Sunday, and
+    /// a dates-only service on that day and the Saturday after.
+    fn new_year() -> Timetable {
+        let mut tt = Timetable::new();
+        tt.add_stop("A", "Mairie & Poste", None);
+        tt.add_stop("B", "Gare", None);
+        let (start, end) = (date(2024, 1, 1), date(2024, 1, 31));
+        tt.add_service("W", WEEKDAYS, start, end);
+        tt.add_exception("W", date(2024, 1, 1), Exception::Deleted);
+        // INFO: removed without anything added, not a holiday.
+        tt.add_exception("W", date(2024, 1, 15), Exception::Deleted);
+        let mut sundays = [false; 7];
+        sundays[6] = true;
+        tt.add_service("SUN", sundays, start, end);
+        tt.add_exception("SUN", date(2024, 1, 1), Exception::Added);
+        tt.add_exception("HOL", date(2024, 1, 1), Exception::Added);
+        tt.add_exception("HOL", date(2024, 1, 6), Exception::Added);
+        tt.add_trip("w2", "W", "R", &[("A", "07:35"), ("B", "07:50")]);
+        tt.add_trip("w1", "W", "R", &[("A", "07:05"), ("B", "07:20")]);
+        tt.add_trip("s1", "SUN", "R", &[("A", "09:00"), ("B", "09:15")]);
+        tt.add_trip("h1", "HOL", "R", &[("A", "10:15"), ("B", "10:30")]);
+        tt.build_service_calendar();
+        tt
+    }
+
+    fn sheet(tt: &Timetable) -> StopSheet {
+        tt.stop_sheet(&SheetQuery {
+            stops: HashSet::from(["A".to_owned()]),
+            from: date(2024, 1, 1),
+            direction: None,
+            pattern_id: None,
+        })
+    }
+
+    #[test]
+    fn holidays_go_to_the_sunday_column() {
+        let tt = new_year();
+        assert_eq!(tt.holidays(), HashSet::from([date(2024, 1, 1)]));
+        let holidays = tt.holidays();
+        assert_eq!(
+            tt.service_day_types("HOL", &holidays),
+            BTreeSet::from([DayType::Saturday, DayType::Sunday])
+        );
+        assert_eq!(
+            tt.service_day_types("SUN", &holidays),
+            BTreeSet::from([DayType::Sunday])
+        );
+    }
+
+    #[test]
+    fn footnotes_are_lettered_down_the_sheet() {
+        let sheet = sheet(&new_year());
+        assert_eq!(
+            sheet.footnotes,
+            [
+                "not on 2024-01-01, 2024-01-15",
+                "also on 2024-01-01",
+                "only on 2024-01-01, 2024-01-06",
+            ]
+        );
+        assert_eq!(
+            sheet.rows(),
+            [
+                (7, vec!["05a 35a".to_owned(), String::new(), String::new()]),
+                (9, vec![String::new(), String::new(), "00b".to_owned()]),
+                (10, vec![String::new(), "15c".to_owned(), "15c".to_owned()]),
+            ]
+        );
+        assert_eq!(
+            [0, 25, 26, 53].map(footnote_mark),
+            ["a", "z", "aa", "bbb"].map(str::to_owned)
+        );
+    }
+
+    #[test]
+    fn renders_the_sheet() {
+        let sheet = sheet(&new_year());
+        assert_eq!(
+            sheet.render(SheetFormat::Text),
+            "\
+Mairie & Poste, towards Gare
+Valid from 2024-01-01 to 2024-01-31
+
+     | Monday to Friday | Saturday | Sunday and holidays
+  07 | 05a 35a          |          |
+  09 |                  |          | 00b
+  10 |                  | 15c      | 15c
+a: not on 2024-01-01, 2024-01-15
+b: also on 2024-01-01
+c: only on 2024-01-01, 2024-01-06
+"
+        );
+        let markdown = sheet.render(SheetFormat::Markdown);
+        assert!(markdown.starts_with("## Mairie & Poste, towards Gare\n"));
+        assert!(markdown.contains(
+            "| Hour | Monday to Friday | Saturday | Sunday and holidays |\n\
+            | ---: | --- | --- | --- |\n\
+            | 07 | 05a 35a |  |  |\n"
+        ));
+        assert!(markdown.ends_with("- **c**: only on 2024-01-01, 2024-01-06\n"));
+        let html = sheet.render(SheetFormat::Html);
+        assert!(html.contains("<h2>Mairie &amp; Poste, towards Gare</h2>"));
+        assert!(html.contains("<tr><th>10</th><td></td><td>15c</td><td>15c</td></tr>"));
+        assert!(html.contains("<li><b>b</b>: also on 2024-01-01</li>"));
+    }
+}
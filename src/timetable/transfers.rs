@@ -0,0 +1,252 @@
+use chrono::Duration;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Time given to change between two trips at the same stop.
+const MIN_TRANSFER_TIME: Duration = Duration::minutes(2);
+/// Time given to walk between two stops of the same station when the feed
+/// doesn't say.
+const STATION_TRANSFER_TIME: Duration = Duration::minutes(5);
+/// Used to time pathways that only have a length, in meters per second.
+const WALKING_SPEED: f32 = 1.2;
+/// Used for pathways with neither a traversal time nor a length, in seconds.
+const PATHWAY_TIME: u32 = 30;
+
+/// A change from one stop to another, from transfers.txt or from the
+/// pathways of a station.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Transfer {
+    pub to_stop_id: String,
+    pub transfer_type: TransferType,
+    /// In seconds.
+    pub min_time: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum TransferType {
+    Recommended,
+    /// The departing vehicle waits for the arriving one.
+    Timed,
+    MinTime,
+    Impossible,
+    /// The rider stays on the vehicle, between trips of the same block.
+    InSeat,
+    /// Trips of the same block, but the rider has to get off and board
+    /// again, as for any other transfer.
+    MustAlight,
+}
+
+impl From<gtfs_structures::TransferType> for TransferType {
+    fn from(value: gtfs_structures::TransferType) -> Self {
+        match value {
+            gtfs_structures::TransferType::Recommended => Self::Recommended,
+            gtfs_structures::TransferType::Timed => Self::Timed,
+            gtfs_structures::TransferType::MinTime => Self::MinTime,
+            gtfs_structures::TransferType::Impossible => Self::Impossible,
+            gtfs_structures::TransferType::StayOnBoard => Self::InSeat,
+            gtfs_structures::TransferType::MustAlight => Self::MustAlight,
+        }
+    }
+}
+
+impl super::Timetable {
+    /// Copies the transfers between extracted stops, and times the walks
+    /// between extracted stops of a station through its pathways. Transfers
+    /// from transfers.txt take precedence over pathways.
+    pub fn import_transfers(&mut self, gtfs: &gtfs_structures::Gtfs) {
+        // INFO: pathways mostly go through entrances and generic nodes that
+        // aren't extracted, so walks are timed on the whole feed.
+        let mut pathways: HashMap<&str, Vec<(&str, u32)>> = HashMap::new();
+        for stop in gtfs.stops.values() {
+            for pathway in &stop.pathways {
+                let time = pathway
+                    .traversal_time
+                    .or(pathway.length.map(|length| (length / WALKING_SPEED) as u32))
+                    .unwrap_or(PATHWAY_TIME);
+                pathways
+                    .entry(&stop.id)
+                    .or_default()
+                    .push((&pathway.to_stop_id, time));
+                if pathway.is_bidirectional == gtfs_structures::PathwayDirectionType::Bidirectional
+                {
+                    pathways
+                        .entry(&pathway.to_stop_id)
+                        .or_default()
+                        .push((&stop.id, time));
+                }
+            }
+        }
+
+        let mut transfers: HashMap<String, Vec<Transfer>> = HashMap::new();
+        for stop_id in self.stops.keys() {
+            let Some(stop) = gtfs.stops.get(stop_id) else {
+                continue;
+            };
+            let mut stop_transfers: Vec<Transfer> = stop
+                .transfers
+                .iter()
+                .filter(|transfer| self.stops.contains_key(&transfer.to_stop_id))
+                .map(|transfer| Transfer {
+                    to_stop_id: transfer.to_stop_id.clone(),
+                    transfer_type: transfer.transfer_type.into(),
+                    min_time: transfer.min_transfer_time,
+                })
+                .collect();
+            for (to_stop_id, time) in walking_times(&pathways, stop_id) {
+                let is_known = stop_transfers
+                    .iter()
+                    .any(|transfer| transfer.to_stop_id == to_stop_id);
+                if to_stop_id != stop_id && !is_known && self.stops.contains_key(to_stop_id) {
+                    stop_transfers.push(Transfer {
+                        to_stop_id: to_stop_id.to_owned(),
+                        transfer_type: TransferType::MinTime,
+                        min_time: Some(time),
+                    });
+                }
+            }
+            if !stop_transfers.is_empty() {
+                transfers.insert(stop_id.clone(), stop_transfers);
+            }
+        }
+        self.transfers = transfers;
+    }
+
+    /// Ids of the stops of each station, by station id, for
+    /// `transfers_from`.
+    pub fn station_stops(&self) -> HashMap<&str, Vec<&str>> {
+        let mut station_stops: HashMap<&str, Vec<&str>> = HashMap::new();
+        for stop in self.stops.values() {
+            if let Some(station) = &stop.parent_station {
+                station_stops.entry(station).or_default().push(&stop.id);
+            }
+        }
+        station_stops
+    }
+
+    /// Stops the rider can change to after getting off at `stop_id`, with
+    /// the time it takes them to be ready to board there. Without transfers
+    /// from the feed, they can stay at the stop or walk to another stop of
+    /// the same station, as listed by `station_stops`.
+    pub fn transfers_from<'a>(
+        &'a self,
+        stop_id: &str,
+        station_stops: &HashMap<&str, Vec<&'a str>>,
+    ) -> Vec<(&'a str, Duration)> {
+        let Some(stop) = self.stops.get(stop_id) else {
+            return vec![];
+        };
+        let mut transfers: HashMap<&str, Duration> = HashMap::new();
+        transfers.insert(&stop.id, MIN_TRANSFER_TIME);
+        if let Some(station) = &stop.parent_station {
+            transfers.extend(
+                station_stops
+                    .get(station.as_str())
+                    .into_iter()
+                    .flatten()
+                    .filter(|other| **other != stop.id)
+                    .map(|other| (*other, STATION_TRANSFER_TIME)),
+            );
+        }
+        for transfer in self.transfers.get(stop_id).into_iter().flatten() {
+            let to_stop_id = transfer.to_stop_id.as_str();
+            let time = match (transfer.transfer_type, transfer.min_time) {
+                (TransferType::Impossible, _) => {
+                    transfers.remove(to_stop_id);
+                    continue;
+                }
+                (TransferType::InSeat, _) => continue,
+                (TransferType::Timed, _) => Duration::zero(),
+                (_, Some(min_time)) => Duration::seconds(min_time.into()),
+                (_, None) if to_stop_id == stop_id => MIN_TRANSFER_TIME,
+                (_, None) => STATION_TRANSFER_TIME,
+            };
+            transfers.insert(to_stop_id, time);
+        }
+        transfers.into_iter().collect()
+    }
+}
+
+/// Shortest walk in seconds from `stop_id` to every stop its pathways lead
+/// to.
+fn walking_times<'a>(
+    pathways: &HashMap<&'a str, Vec<(&'a str, u32)>>,
+    stop_id: &'a str,
+) -> HashMap<&'a str, u32> {
+    let mut times = HashMap::from([(stop_id, 0)]);
+    let mut queue = BinaryHeap::from([(std::cmp::Reverse(0), stop_id)]);
+    while let Some((std::cmp::Reverse(time), stop_id)) = queue.pop() {
+        if times.get(stop_id).is_some_and(|known| *known < time) {
+            continue;
+        }
+        for (next_stop_id, walk) in pathways.get(stop_id).into_iter().flatten() {
+            let next_time = time + walk;
+            if times
+                .get(next_stop_id)
+                .is_some_and(|known| *known <= next_time)
+            {
+                continue;
+            }
+            times.insert(next_stop_id, next_time);
+            queue.push((std::cmp::Reverse(next_time), next_stop_id));
+        }
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::Timetable;
+
+    #[test]
+    fn walks_take_the_shortest_way_through_pathways() {
+        // INFO: the entrance isn't a stop riders board at, it's still on
+        // the way.
+        let pathways = HashMap::from([
+            ("Q1", vec![("E", 60), ("Q2", 200)]),
+            ("E", vec![("Q2", 90)]),
+            ("Q2", vec![("Q3", 30)]),
+            ("Q4", vec![("Q1", 10)]),
+        ]);
+        assert_eq!(
+            walking_times(&pathways, "Q1"),
+            HashMap::from([("Q1", 0), ("E", 60), ("Q2", 150), ("Q3", 180)])
+        );
+    }
+
+    #[test]
+    fn riders_get_off_for_must_alight_transfers() {
+        assert_eq!(
+            TransferType::from(gtfs_structures::TransferType::MustAlight),
+            TransferType::MustAlight
+        );
+        assert_eq!(
+            TransferType::from(gtfs_structures::TransferType::StayOnBoard),
+            TransferType::InSeat
+        );
+
+        let mut tt = Timetable::new();
+        tt.add_stop("Q1", "Gare", Some("S"));
+        tt.add_stop("Q2", "Gare", Some("S"));
+        tt.add_stop("Q3", "Mairie", None);
+        let transfer = |to_stop_id: &str, transfer_type| Transfer {
+            to_stop_id: to_stop_id.to_owned(),
+            transfer_type,
+            min_time: None,
+        };
+        tt.transfers.insert(
+            "Q1".to_owned(),
+            vec![
+                transfer("Q1", TransferType::InSeat),
+                transfer("Q2", TransferType::Impossible),
+                transfer("Q3", TransferType::MustAlight),
+            ],
+        );
+        let station_stops = tt.station_stops();
+        let mut transfers = tt.transfers_from("Q1", &station_stops);
+        transfers.sort();
+        assert_eq!(
+            transfers,
+            [("Q1", MIN_TRANSFER_TIME), ("Q3", STATION_TRANSFER_TIME)]
+        );
+    }
+}
@@ -1,66 +1,327 @@
+use super::name_rules::{station_ids, Candidate, NamePolicy, NameRules, Rename};
+use super::stop_search::normalize_name;
+use std::collections::HashMap;
+
 impl super::Timetable {
     // INFO: the dataset I tested this code with has an issues where individual
     // stops don't always have the same spelling. This functions goal is to make
     // the spelling uniform.
-    pub fn uniformise_stop_names(&mut self) {
-        let iter = self
-            .stops
+    /// Stops are grouped by normalized name in a single pass, then every stop
+    /// of a group gets the spelling picked by `rules`. Returns every rename.
+    pub fn uniformise_stop_names(&mut self, rules: &NameRules) -> Vec<Rename> {
+        let mut uses: HashMap<&str, usize> = HashMap::new();
+        for stop_time in self.trips.iter().flat_map(|(_, trip)| &trip.stop_times) {
+            *uses.entry(stop_time.name.as_str()).or_default() += 1;
+        }
+        let stations = station_ids(&self.stops);
+        // INFO: a parent station may be spelled unlike any of its stops, its
+        // name is only worth offering when the rules look for it.
+        let offers_stations = rules.policies.contains(&NamePolicy::ParentStation);
+        let mut groups: HashMap<String, Vec<&str>> = HashMap::new();
+        for (id, stop) in &self.stops {
+            if let Some(name) = &stop.name {
+                groups.entry(normalize_name(name)).or_default().push(id);
+            }
+        }
+
+        let mut renames = vec![];
+        for (normalized, ids) in &groups {
+            let mut candidates: Vec<Candidate> = vec![];
+            for id in ids {
+                let stop = &self.stops[*id];
+                let Some(name) = stop.name.as_deref() else {
+                    continue;
+                };
+                let parent_name = stop
+                    .parent_station
+                    .as_ref()
+                    .filter(|_| offers_stations)
+                    .and_then(|parent| self.stops.get(parent)?.name.as_deref());
+                let spellings = [(name, stations.contains(id))]
+                    .into_iter()
+                    .chain(parent_name.map(|name| (name, true)));
+                for (name, is_station) in spellings {
+                    match candidates
+                        .iter_mut()
+                        .find(|candidate| candidate.name == name)
+                    {
+                        Some(candidate) => candidate.is_station |= is_station,
+                        None => candidates.push(Candidate {
+                            name,
+                            uses: uses.get(name).copied().unwrap_or_default(),
+                            is_station,
+                        }),
+                    }
+                }
+            }
+            let Some(picked) = rules.pick(&candidates) else {
+                continue;
+            };
+            for id in ids {
+                let Some(name) = &self.stops[*id].name else {
+                    continue;
+                };
+                let (to, reason) = match rules.override_for(id, normalized) {
+                    Some(name) => (name.clone(), "override".to_owned()),
+                    None => {
+                        let Some(current) =
+                            candidates.iter().find(|candidate| candidate.name == name)
+                        else {
+                            continue;
+                        };
+                        let to = picked.name.split_whitespace().collect::<Vec<_>>().join(" ");
+                        (to, rules.reason(picked, current))
+                    }
+                };
+                if *name != to {
+                    renames.push(Rename {
+                        stop_id: id.to_string(),
+                        from: name.clone(),
+                        to,
+                        reason,
+                    });
+                }
+            }
+        }
+        renames.sort_by(|a, b| a.stop_id.cmp(&b.stop_id));
+        if renames.is_empty() {
+            return renames;
+        }
+
+        for rename in &renames {
+            if let Some(stop) = self.stops.get_mut(&rename.stop_id) {
+                stop.name = Some(rename.to.clone());
+            }
+        }
+        let new_names: HashMap<&str, &str> = renames
             .iter()
-            .filter_map(|(id, stop)| stop.name.as_ref().map(|name| (id, name)));
-        let mut colliding: Vec<(String, String)> = vec![];
-        for (id, name) in iter {
-            let mut tmp_colliding: Vec<_> = self
-                .stops
+            .map(|rename| (rename.stop_id.as_str(), rename.to.as_str()))
+            .collect();
+        self.trips
+            .iter_mut()
+            .flat_map(|(_, trip)| &mut trip.stop_times)
+            .for_each(|stop_time| {
+                if let Some(name) = new_names.get(stop_time.stop_id.as_str()) {
+                    stop_time.name = name.to_string();
+                }
+            });
+        // INFO: pattern labels and station names are made of stop names.
+        self.build_route_patterns();
+        self.build_stations();
+        renames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::timetable::fixtures::{date, WEEKDAYS};
+    use crate::timetable::my_gtfs_structs::Stop;
+    use crate::timetable::name_rules::{NamePolicy, NameRules};
+    use crate::timetable::service_time::ServiceTime;
+    use crate::timetable::stop_search::normalize_name;
+    use crate::timetable::{StopTime, Timetable, Trip};
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    /// `stop_count` stops served ten by ten, one in ten having a twin spelled
+    /// without accents.
+    fn timetable(stop_count: usize) -> Timetable {
+        let mut tt = Timetable::new();
+        let mut stop_ids = vec![];
+        for index in 0..stop_count {
+            let mut names = vec![format!("Arrêt Élysée {index}")];
+            if index % 10 == 0 {
+                names.push(format!("Arret Elysee {index}"));
+            }
+            for (copy, name) in names.into_iter().enumerate() {
+                let id = format!("{index}:{copy}");
+                tt.stops.insert(
+                    id.clone(),
+                    Stop {
+                        id: id.clone(),
+                        code: None,
+                        name: Some(name),
+                        description: None,
+                        parent_station: None,
+                        zone_id: None,
+                        url: None,
+                        longitude: None,
+                        latitude: None,
+                        timezone: None,
+                        level_id: None,
+                        platform_code: None,
+                        tts_name: None,
+                    },
+                );
+                stop_ids.push(id);
+            }
+        }
+        for (index, chunk) in stop_ids.chunks(10).enumerate() {
+            let stop_times = chunk
                 .iter()
-                .filter(|(filter_id, _)| *filter_id != id)
-                .filter(|(filter_id, _)| {
-                    colliding
-                        .iter()
-                        .any(|(a, b)| *a == **filter_id || *b == **filter_id)
-                })
-                .filter(|(_, filter_item)| {
-                    if let Some(filter_name) = &filter_item.name {
-                        if *filter_name != *name {
-                            let lhs = unidecode::unidecode(filter_name).to_lowercase();
-                            let rhs = unidecode::unidecode(name).to_lowercase();
-                            if lhs == rhs {
-                                println!("{filter_name} == {name}");
-                                return true;
-                            }
-                        }
+                .enumerate()
+                .map(|(position, stop_id)| {
+                    let time = ServiceTime::from_seconds(8 * 3600 + 60 * position as u32);
+                    StopTime {
+                        arrival: time,
+                        departure: time,
+                        stop_id: stop_id.clone(),
+                        name: tt.stops[stop_id].name.clone().unwrap_or_default(),
+                        parent_station: None,
+                        platform_code: None,
                     }
-                    false
                 })
-                .map(|(fe_id, _)| (id.clone(), fe_id.clone()))
                 .collect();
-            tmp_colliding.drain(..).for_each(|a| colliding.push(a));
+            let id = format!("trip {index}");
+            tt.trips.insert(
+                id.clone(),
+                Trip {
+                    id,
+                    service_id: "S".to_owned(),
+                    route_id: "R".to_owned(),
+                    stop_times,
+                    headsign: None,
+                    short_name: None,
+                    direction: None,
+                },
+            );
         }
-        colliding
-            .drain(..)
-            .for_each(|ids| self.keep_longest_stop_name(ids));
+        tt
     }
 
-    /// Replace name of the stop that is the shortest in bytes as it's most
-    /// of the time the one that lacks the diacritics.
-    fn keep_longest_stop_name(&mut self, ids: (String, String)) {
-        assert!(ids.0 != ids.1);
-        let (Some(stop1), Some(stop2)) = (self.stops.get(&ids.0), self.stops.get(&ids.1)) else {
-            return;
-        };
-        let (Some(name1), Some(name2)) = (&stop1.name, &stop2.name) else {
-            return;
-        };
-        let (id, name) = if name1.len() > name2.len() {
-            (ids.1, name1.clone())
-        } else {
-            (ids.0, name2.clone())
+    fn quays() -> Timetable {
+        let mut tt = Timetable::new();
+        tt.add_service("wk", WEEKDAYS, date(2026, 10, 1), date(2026, 12, 31));
+        tt.add_stop("CH", "Gare de Chelles", None);
+        tt.add_stop("CH1", "Chelles Gare", Some("CH"));
+        tt.add_stop("CH2", "Chelles Gare", Some("CH"));
+        tt.add_stop("CH3", "Chellés Gare", Some("CH"));
+        tt.add_stop("T", "Torcy  RER", None);
+        tt.add_trip("t1", "wk", "r", &[("CH1", "08:00"), ("T", "08:10")]);
+        tt.add_trip("t2", "wk", "r", &[("CH2", "09:00"), ("CH3", "09:10")]);
+        tt
+    }
+
+    fn report(renames: &[crate::timetable::name_rules::Rename]) -> Vec<String> {
+        renames.iter().map(|rename| rename.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_the_longest_spelling_by_default() {
+        let mut tt = quays();
+        let renames = tt.uniformise_stop_names(&NameRules::default());
+        assert_eq!(
+            report(&renames),
+            [
+                "CH1\tChelles Gare\tChellés Gare\tlongest",
+                "CH2\tChelles Gare\tChellés Gare\tlongest",
+                "T\tTorcy  RER\tTorcy RER\twhitespace",
+            ]
+        );
+        let names: Vec<_> = tt.trips.get_vec("t2").unwrap()[0]
+            .stop_times
+            .iter()
+            .map(|stop_time| stop_time.name.as_str())
+            .collect();
+        assert_eq!(names, ["Chellés Gare", "Chellés Gare"]);
+    }
+
+    #[test]
+    fn quays_take_the_name_of_their_parent_station() {
+        let mut tt = quays();
+        let rules = NameRules {
+            policies: vec![NamePolicy::ParentStation],
+            ..Default::default()
         };
-        let stop = self.stops.get_mut(&id).expect("stop to still be there");
-        stop.name = Some(name.clone());
-        self.trips
-            .iter_mut()
-            .flat_map(|(_, trip)| &mut trip.stop_times)
-            .filter(|stop_time| stop_time.stop_id == id)
-            .for_each(|stop_time| stop_time.name = name.clone());
+        let renames = tt.uniformise_stop_names(&rules);
+        assert_eq!(
+            report(&renames),
+            [
+                "CH1\tChelles Gare\tGare de Chelles\tparent-station",
+                "CH2\tChelles Gare\tGare de Chelles\tparent-station",
+                "CH3\tChellés Gare\tGare de Chelles\tparent-station",
+                "T\tTorcy  RER\tTorcy RER\twhitespace",
+            ]
+        );
+    }
+
+    #[test]
+    fn overrides_win_over_policies() {
+        let mut tt = quays();
+        let mut rules = NameRules::default();
+        rules
+            .overrides
+            .insert("CH2".to_owned(), "Chelles Gare RER".to_owned());
+        let renames = tt.uniformise_stop_names(&rules);
+        assert_eq!(
+            report(&renames),
+            [
+                "CH1\tChelles Gare\tChellés Gare\tlongest",
+                "CH2\tChelles Gare\tChelles Gare RER\toverride",
+                "T\tTorcy  RER\tTorcy RER\twhitespace",
+            ]
+        );
+    }
+
+    /// Names the stops got from the pairwise comparison
+    /// `uniformise_stop_names` used to make, with its filter fixed to look
+    /// at every pair: the shorter of two names normalized alike takes the
+    /// longer one.
+    fn pairwise_names(tt: &Timetable) -> HashMap<String, String> {
+        let mut names: HashMap<String, String> = tt
+            .stops
+            .iter()
+            .filter_map(|(id, stop)| Some((id.clone(), stop.name.clone()?)))
+            .collect();
+        let ids: Vec<String> = names.keys().cloned().collect();
+        for a in &ids {
+            for b in ids.iter().filter(|b| *b != a) {
+                let (name_a, name_b) = (&names[a], &names[b]);
+                if name_a.len() > name_b.len() && normalize_name(name_a) == normalize_name(name_b) {
+                    names.insert(b.clone(), name_a.clone());
+                }
+            }
+        }
+        names
+    }
+
+    fn names(tt: &Timetable) -> HashMap<String, String> {
+        tt.stops
+            .iter()
+            .filter_map(|(id, stop)| Some((id.clone(), stop.name.clone()?)))
+            .collect()
+    }
+
+    #[test]
+    fn grouping_names_stops_as_comparing_pairs_did() {
+        let mut tt = timetable(300);
+        let expected = pairwise_names(&tt);
+        let renames = tt.uniformise_stop_names(&NameRules::default());
+        assert_eq!(renames.len(), 30);
+        assert_eq!(names(&tt), expected);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with `cargo test --release -- --ignored --nocapture`"]
+    fn uniformise_stop_names_benchmark() {
+        // INFO: timings are printed rather than checked, they depend too
+        // much on the machine to make a reliable test.
+        for stop_count in [1_000, 3_000] {
+            let tt = timetable(stop_count);
+            let started = Instant::now();
+            pairwise_names(&tt);
+            println!("pairwise, {stop_count} stops: {:?}", started.elapsed());
+        }
+        for stop_count in [1_000, 3_000, 30_000] {
+            let mut tt = timetable(stop_count);
+            let started = Instant::now();
+            let renames = tt.uniformise_stop_names(&NameRules::default());
+            println!(
+                "grouped, {stop_count} stops: {} renames in {:?}",
+                renames.len(),
+                started.elapsed()
+            );
+            assert_eq!(renames.len(), stop_count / 10);
+        }
     }
 }
@@ -0,0 +1,130 @@
+use super::my_gtfs_structs::Exception;
+use std::collections::HashSet;
+
+impl super::Timetable {
+    /// Looks for data that would make queries silently wrong, returns one
+    /// human readable line per issue found.
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues = vec![];
+        if self.trips.is_empty() {
+            issues.push("timetable has no trip".to_owned());
+        }
+        issues.extend(self.check_service_calendar());
+        for (id, trip) in self.trips.iter() {
+            if !self.calendar.contains_key(&trip.service_id)
+                && !self.calendar_dates.contains_key(&trip.service_id)
+            {
+                issues.push(format!(
+                    "trip {id}: service {} has neither a calendar nor calendar dates",
+                    trip.service_id
+                ));
+            }
+            if self.is_dates_only_service(&trip.service_id)
+                && !self.calendar_dates[&trip.service_id]
+                    .iter()
+                    .any(|date| date.exception_type == Exception::Added)
+            {
+                issues.push(format!(
+                    "trip {id}: service {} is defined by dates only and never added",
+                    trip.service_id
+                ));
+            }
+            if trip.stop_times.is_empty() {
+                issues.push(format!("trip {id}: no stop time"));
+            }
+            if trip
+                .stop_times
+                .windows(2)
+                .any(|pair| pair[0].departure > pair[1].arrival)
+            {
+                issues.push(format!("trip {id}: stop times are not in order"));
+            }
+            for stop_time in &trip.stop_times {
+                if stop_time.departure < stop_time.arrival {
+                    issues.push(format!(
+                        "trip {id}: leaves {} before arriving",
+                        stop_time.stop_id
+                    ));
+                }
+            }
+            for stop_time in &trip.stop_times {
+                if !self.stops.contains_key(&stop_time.stop_id) {
+                    issues.push(format!("trip {id}: unknown stop {}", stop_time.stop_id));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Compares the calendar dates of every service used by the trips with
+    /// the ones of the `gtfs` they were extracted from, returns one line per
+    /// date that did not make it.
+    pub fn missing_calendar_dates(&self, gtfs: &gtfs_structures::Gtfs) -> Vec<String> {
+        let service_ids: HashSet<_> = self
+            .trips
+            .iter()
+            .map(|(_, trip)| trip.service_id.as_str())
+            .collect();
+        let mut issues = vec![];
+        for (feed_service_id, calendar_dates) in &gtfs.calendar_dates {
+            // INFO: ids are simplified when writing the timetable to a file.
+            let service_id = super::simplify_ids(feed_service_id);
+            if !service_ids.contains(service_id.as_str()) {
+                continue;
+            }
+            let stored = self.calendar_dates.get(&service_id);
+            for calendar_date in calendar_dates {
+                let exception_type: Exception = calendar_date.exception_type.into();
+                let is_stored = stored.is_some_and(|stored| {
+                    stored.iter().any(|candidate| {
+                        candidate.date == calendar_date.date
+                            && candidate.exception_type == exception_type
+                    })
+                });
+                if !is_stored {
+                    issues.push(format!(
+                        "service {service_id}: calendar date {} ({exception_type:?}) is missing",
+                        calendar_date.date
+                    ));
+                }
+            }
+        }
+        issues
+    }
+
+    /// Checks the precomputed service calendar against the calendar and
+    /// calendar dates it was built from.
+    fn check_service_calendar(&self) -> Vec<String> {
+        let calendar = &self.service_calendar;
+        let (Some(start_date), Some(end_date)) = (calendar.start_date, calendar.end_date) else {
+            return vec!["timetable has no service calendar".to_owned()];
+        };
+        let mut issues = vec![];
+        for (service_id, active_dates) in &calendar.services {
+            if active_dates.count() == 0 {
+                issues.push(format!("service {service_id} never runs"));
+            }
+            let mismatch = start_date
+                .iter_days()
+                .take_while(|date| *date <= end_date)
+                .find(|date| {
+                    calendar.day(*date).is_some_and(|day| {
+                        active_dates.contains(day) != self.runs_on_uncached(service_id, *date)
+                    })
+                });
+            if let Some(date) = mismatch {
+                issues.push(format!(
+                    "service {service_id}: service calendar is wrong on {date}"
+                ));
+            }
+        }
+        for service_id in self.calendar.keys().chain(self.calendar_dates.keys()) {
+            if !calendar.services.contains_key(service_id) {
+                issues.push(format!(
+                    "service {service_id} is missing from the service calendar"
+                ));
+            }
+        }
+        issues
+    }
+}